@@ -1,21 +1,52 @@
+use crate::core::mmu::boot::Model;
+use crate::core::mmu::save;
+use crate::error::{Error, Result};
+use crate::interface::input::KeyBindings;
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Settings read from `config.toml`. Every field is optional in the file;
+/// missing ones take their default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub system: SystemConfig,
     pub audio: AudioConfig,
     pub video: VideoConfig,
     pub input: InputConfig,
+    /// Where the config was loaded from and `save` writes to
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SystemConfig {
     pub debug_mode: bool,
     pub save_state_path: String,
     pub rom_path: String,
+    /// Directory for battery-backed `.sav` files; `None` keeps them next to the ROM
+    pub save_dir: Option<String>,
+    /// Hardware model whose post-boot state is used when no boot ROM runs
+    pub model: Model,
+    /// Run the boot ROM at `bootrom_path` before the cartridge
+    pub bootrom_enabled: bool,
+    pub bootrom_path: Option<String>,
+    /// Write a Gameboy Doctor instruction trace to this file
+    pub trace_path: Option<String>,
+    /// Wait for another emulator to connect a link cable on this TCP address
+    pub link_listen: Option<String>,
+    /// Connect a link cable to another emulator listening on this TCP address
+    pub link_connect: Option<String>,
+    /// Memory for rewind history in MB; 0 turns rewind off
+    pub rewind_buffer_mb: u32,
+    /// Frames between rewind snapshots
+    pub rewind_interval: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AudioConfig {
     pub enabled: bool,
     pub volume: f32,
@@ -23,18 +54,21 @@ pub struct AudioConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VideoConfig {
     pub scale: u32,
     pub color_correction: bool,
     pub frame_blend: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct InputConfig {
     pub keyboard_mapping: KeyboardMapping,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KeyboardMapping {
     pub up: String,
     pub down: String,
@@ -46,23 +80,21 @@ pub struct KeyboardMapping {
     pub select: String,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            system: SystemConfig::default(),
-            audio: AudioConfig::default(),
-            video: VideoConfig::default(),
-            input: InputConfig::default(),
-        }
-    }
-}
-
 impl Default for SystemConfig {
     fn default() -> Self {
         Self {
             debug_mode: false,
             save_state_path: "saves".to_string(),
             rom_path: "roms".to_string(),
+            save_dir: None,
+            model: Model::Dmg,
+            bootrom_enabled: false,
+            bootrom_path: None,
+            trace_path: None,
+            link_listen: None,
+            link_connect: None,
+            rewind_buffer_mb: 64,
+            rewind_interval: 1,
         }
     }
 }
@@ -87,14 +119,6 @@ impl Default for VideoConfig {
     }
 }
 
-impl Default for InputConfig {
-    fn default() -> Self {
-        Self {
-            keyboard_mapping: KeyboardMapping::default(),
-        }
-    }
-}
-
 impl Default for KeyboardMapping {
     fn default() -> Self {
         Self {
@@ -111,13 +135,130 @@ impl Default for KeyboardMapping {
 }
 
 impl Config {
-    pub fn load() -> Result<Self, anyhow::Error> {
-        // TODO: Implement config loading from file
-        Ok(Self::default())
+    /// `config.toml` in the platform config directory, e.g.
+    /// `~/.config/gameboy_emulator` on Linux
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .map(|dir| dir.join("gameboy_emulator"))
+            .unwrap_or_default()
+            .join("config.toml")
+    }
+
+    /// Load from `path`, or from `default_path` when none is given. A missing
+    /// file is created with the defaults so there is something to edit.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let config = Self::read(path)?;
+        if let Some(path) = config.path.as_deref().filter(|path| !path.exists()) {
+            config.save_to(path)?;
+        }
+        Ok(config)
+    }
+
+    /// Like `load`, but a missing file just gives the defaults and nothing
+    /// is written to disk
+    pub fn read(path: Option<&Path>) -> Result<Self> {
+        let path = path.map_or_else(Self::default_path, Path::to_path_buf);
+        let mut config = if path.exists() {
+            let text = fs::read_to_string(&path)?;
+            Self::parse(&text).map_err(|e| match e {
+                Error::Config(message) => Error::Config(format!("{}: {}", path.display(), message)),
+                e => e,
+            })?
+        } else {
+            Self::default()
+        };
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// Parse and check the contents of a config file
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text).map_err(|e| Error::Config(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
     }
 
-    pub fn save(&self) -> Result<(), anyhow::Error> {
-        // TODO: Implement config saving to file
+    /// Reject values the emulator can't use, naming the offending field
+    pub fn validate(&self) -> Result<()> {
+        let invalid =
+            |field: &str, reason: &str| Err(Error::Config(format!("{}: {}", field, reason)));
+        if self.system.rewind_interval == 0 {
+            return invalid("system.rewind_interval", "must be at least 1");
+        }
+        if !(0.0..=1.0).contains(&self.audio.volume) {
+            return invalid("audio.volume", "must be between 0.0 and 1.0");
+        }
+        if self.audio.sample_rate == 0 {
+            return invalid("audio.sample_rate", "must be greater than 0");
+        }
+        if !(1..=10).contains(&self.video.scale) {
+            return invalid("video.scale", "must be between 1 and 10");
+        }
+        KeyBindings::from_mapping(&self.input.keyboard_mapping)?;
         Ok(())
     }
+
+    /// Write back to the file the config was loaded from, if any
+    pub fn save(&self) -> Result<()> {
+        match &self.path {
+            Some(path) => self.save_to(path),
+            None => Ok(()),
+        }
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string_pretty(self).map_err(|e| Error::Config(e.to_string()))?;
+        save::write_atomic(path, text.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip_and_errors() {
+        let mut config = Config::default();
+        config.system.rewind_interval = 4;
+        config.input.keyboard_mapping.a = "K".to_string();
+        let text = toml::to_string_pretty(&config).unwrap();
+        let parsed = Config::parse(&text).unwrap();
+        assert_eq!(parsed.system.rewind_interval, 4);
+        assert_eq!(parsed.input.keyboard_mapping.a, "K");
+
+        // Missing fields keep their defaults
+        let partial = Config::parse("[video]\nscale = 2\n").unwrap();
+        assert_eq!(partial.video.scale, 2);
+        assert_eq!(partial.input.keyboard_mapping.start, "Return");
+
+        let error = |text: &str| match Config::parse(text) {
+            Err(Error::Config(message)) => message,
+            other => panic!("expected a config error, got {:?}", other),
+        };
+        assert!(error("[input.keyboard_mapping]\nselect = \"Shfit\"\n")
+            .contains("input.keyboard_mapping.select"));
+        assert_eq!(
+            error("[input.keyboard_mapping]\na = \"P\"\n"),
+            "input.keyboard_mapping.a: \"P\" is reserved for pause"
+        );
+        assert!(error("[input.keyboard_mapping]\nb = \"X\"\n")
+            .contains("already bound to input.keyboard_mapping.a"));
+        assert!(error("[video]\nscale = 0\n").contains("video.scale"));
+        assert!(error("[system]\nrewind_interval = \"fast\"\n").contains("rewind_interval"));
+    }
+
+    #[test]
+    fn test_only_load_creates_missing_config() {
+        let dir = std::env::temp_dir().join(format!("gb_config_test_{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let _ = fs::remove_dir_all(&dir);
+
+        let config = Config::read(Some(&path)).unwrap();
+        assert_eq!(config.path.as_deref(), Some(path.as_path()));
+        assert!(!path.exists());
+
+        Config::load(Some(&path)).unwrap();
+        assert!(path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,3 +1,5 @@
+use crate::core::cycles::{CyclesType, Tick};
+use crate::core::state::{SaveState, StateReader, StateWriter};
 use crate::error::Result;
 use crate::interface::audio::AudioInterface;
 
@@ -8,6 +10,7 @@ pub struct APU {
     pub enabled: bool,
     cycles: u32,
     enable_flags: u8,
+    muted: bool, // 主機端設定（例如快轉時靜音），不屬於存檔狀態
 }
 
 impl APU {
@@ -17,6 +20,7 @@ impl APU {
             enabled: false,
             cycles: 0,
             enable_flags: 0,
+            muted: false,
         }
     }
 
@@ -28,7 +32,7 @@ impl APU {
         self.cycles += cycles;
         // 每 4194304/60 個週期（約 70224）產生一次音訊樣本
         while self.cycles >= 70224 {
-            if let Some(output) = &mut self.audio_output {
+            if let Some(output) = self.audio_output.as_mut().filter(|_| !self.muted) {
                 // 簡化版本：推送靜音樣本
                 output.push_sample(0.0);
             }
@@ -77,4 +81,31 @@ impl APU {
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
     }
+
+    // 靜音時照常運作，只是不把樣本送到輸出
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+}
+
+impl Tick for APU {
+    fn tick(&mut self, cycles: CyclesType) -> Result<()> {
+        self.step(cycles)?;
+        Ok(())
+    }
+}
+
+impl SaveState for APU {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.u32(self.cycles);
+        w.u8(self.enable_flags);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.enabled = r.bool()?;
+        self.cycles = r.u32()?;
+        self.enable_flags = r.u8()?;
+        Ok(())
+    }
 }
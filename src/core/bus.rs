@@ -0,0 +1,199 @@
+// Memory bus seen by the CPU
+
+use crate::core::audio::apu::APU;
+use crate::core::cycles::{CyclesType, Tick};
+use crate::core::mmu::MMU;
+use crate::core::ppu::PPU;
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::Result;
+use crate::interface::{audio::AudioInterface, video::VideoInterface};
+
+/// Everything behind the CPU's address and data pins. The CPU ticks the bus
+/// once per M-cycle before each access, so hardware behind it stays in step.
+pub trait Bus: Tick {
+    /// Read as part of a CPU M-cycle
+    fn read(&mut self, address: u16) -> Result<u8>;
+
+    /// Write as part of a CPU M-cycle
+    fn write(&mut self, address: u16, value: u8) -> Result<()>;
+
+    /// Read without side effects, for tracers and debuggers
+    fn peek(&self, address: u16) -> Result<u8>;
+
+    /// IE (0xFFFF)
+    fn interrupt_enable(&self) -> u8;
+
+    /// IF (0xFF0F)
+    fn interrupt_flags(&self) -> u8;
+
+    fn set_interrupt_flags(&mut self, value: u8);
+}
+
+/// The console's own bus: the MMU, with the PPU and APU clocked alongside it.
+/// The MMU in turn clocks the timer, serial port, OAM DMA and cartridge.
+#[derive(Debug)]
+pub struct SystemBus {
+    pub mmu: MMU,
+    pub ppu: PPU,
+    pub apu: APU,
+}
+
+impl SystemBus {
+    pub fn new(video: Box<dyn VideoInterface>, audio: Option<Box<dyn AudioInterface>>) -> Self {
+        Self {
+            mmu: MMU::new(),
+            ppu: PPU::new(video),
+            apu: APU::new(audio),
+        }
+    }
+
+    /// Power-on state for everything behind the bus
+    pub fn reset(&mut self) -> Result<()> {
+        self.mmu.reset();
+        self.ppu.reset()?;
+        self.apu.reset()
+    }
+}
+
+impl Tick for SystemBus {
+    /// The one clocking routine every component runs from
+    fn tick(&mut self, cycles: CyclesType) -> Result<()> {
+        self.mmu.step(cycles);
+        self.ppu.step(&mut self.mmu, cycles)?;
+        self.apu.tick(cycles)
+    }
+}
+
+impl SaveState for SystemBus {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.mmu.save_state(w);
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.mmu.load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)
+    }
+}
+
+impl Bus for SystemBus {
+    fn read(&mut self, address: u16) -> Result<u8> {
+        self.mmu.read_byte(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> Result<()> {
+        self.mmu.write_byte(address, value)
+    }
+
+    fn peek(&self, address: u16) -> Result<u8> {
+        self.mmu.peek(address)
+    }
+
+    fn interrupt_enable(&self) -> u8 {
+        self.mmu.interrupt_enable
+    }
+
+    fn interrupt_flags(&self) -> u8 {
+        self.mmu.interrupt_flags
+    }
+
+    fn set_interrupt_flags(&mut self, value: u8) {
+        self.mmu.interrupt_flags = value;
+    }
+}
+
+/// What the bus did during one M-cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusActivity {
+    Idle,
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+/// 64KB of plain RAM with no hardware behind it, recording every M-cycle.
+/// Used to test the CPU in isolation.
+#[derive(Debug, Clone)]
+pub struct FlatBus {
+    memory: Vec<u8>,
+    activity: Vec<BusActivity>,
+}
+
+impl FlatBus {
+    pub fn new() -> Self {
+        Self {
+            memory: vec![0; 0x10000],
+            activity: Vec::new(),
+        }
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Direct access that doesn't show up in the activity log
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// M-cycles since the last `clear_activity`
+    pub fn activity(&self) -> &[BusActivity] {
+        &self.activity
+    }
+
+    pub fn clear_activity(&mut self) {
+        self.activity.clear();
+    }
+
+    /// Fill in the current M-cycle, or start one for an access without a tick
+    fn record(&mut self, access: BusActivity) {
+        match self.activity.last_mut() {
+            Some(last @ BusActivity::Idle) => *last = access,
+            _ => self.activity.push(access),
+        }
+    }
+}
+
+impl Default for FlatBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tick for FlatBus {
+    fn tick(&mut self, _cycles: CyclesType) -> Result<()> {
+        self.activity.push(BusActivity::Idle);
+        Ok(())
+    }
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, address: u16) -> Result<u8> {
+        let value = self.memory[address as usize];
+        self.record(BusActivity::Read(address, value));
+        Ok(value)
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> Result<()> {
+        self.memory[address as usize] = value;
+        self.record(BusActivity::Write(address, value));
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8> {
+        Ok(self.memory[address as usize])
+    }
+
+    fn interrupt_enable(&self) -> u8 {
+        self.memory[0xFFFF]
+    }
+
+    fn interrupt_flags(&self) -> u8 {
+        self.memory[0xFF0F]
+    }
+
+    fn set_interrupt_flags(&mut self, value: u8) {
+        self.memory[0xFF0F] = value;
+    }
+}
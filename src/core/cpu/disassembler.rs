@@ -0,0 +1,386 @@
+// SM83 disassembler producing RGBDS-style listings
+
+use super::instructions::decode::{
+    decode, decode_cb, AluOp, CbOp, Condition, Indirect, Instruction,
+};
+use crate::core::bus::Bus;
+use crate::error::{Error, RegTarget, Result};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+/// Label names substituted for addresses in disassembly
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    names: HashMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, address: u16, name: impl Into<String>) {
+        self.names.insert(address, name.into());
+    }
+
+    pub fn get(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    /// Read an RGBDS/BGB `.sym` file (`BB:AAAA Name` per line). Switchable
+    /// ROM symbols are only kept for `bank`, since they all share 0x4000-0x7FFF.
+    pub fn parse_sym(text: &str, bank: u16) -> Self {
+        let mut symbols = Self::new();
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            let Some((location, name)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let Some((sym_bank, address)) = location.split_once(':') else {
+                continue;
+            };
+            let (Ok(sym_bank), Ok(address)) = (
+                u16::from_str_radix(sym_bank, 16),
+                u16::from_str_radix(address, 16),
+            ) else {
+                continue;
+            };
+            if (0x4000..0x8000).contains(&address) && sym_bank != bank {
+                continue;
+            }
+            symbols.insert(address, name.trim());
+        }
+        symbols
+    }
+}
+
+/// An instruction decoded at a specific address together with its immediate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembled {
+    pub address: u16,
+    pub instruction: Instruction,
+    /// n8/e8 in the low byte or n16; zero when the instruction has none
+    pub immediate: u16,
+}
+
+impl Disassembled {
+    pub fn length(&self) -> u16 {
+        self.instruction.length()
+    }
+
+    /// RGBDS-style text, with addresses replaced by names from `symbols`
+    pub fn format(&self, symbols: Option<&Symbols>) -> String {
+        let address = |value: u16| match symbols.and_then(|s| s.get(value)) {
+            Some(name) => name.to_string(),
+            None => format!("${:04X}", value),
+        };
+        let n8 = format!("${:02X}", self.immediate as u8);
+        let e8 = self.immediate as u8 as i8;
+        let n16 = address(self.immediate);
+
+        match self.instruction {
+            Instruction::Nop => "nop".to_string(),
+            Instruction::Stop => "stop".to_string(),
+            Instruction::Halt => "halt".to_string(),
+            Instruction::Di => "di".to_string(),
+            Instruction::Ei => "ei".to_string(),
+
+            Instruction::Ld(target, source) => format!("ld {}, {}", r8(target), r8(source)),
+            Instruction::LdImm(target) => format!("ld {}, {}", r8(target), n8),
+            Instruction::LdRrImm(rr) => format!("ld {}, {}", r16(rr), n16),
+            Instruction::LdIndirectA(rr) => format!("ld [{}], a", indirect(rr)),
+            Instruction::LdAIndirect(rr) => format!("ld a, [{}]", indirect(rr)),
+            Instruction::LdAddrA => format!("ld [{}], a", n16),
+            Instruction::LdAAddr => format!("ld a, [{}]", n16),
+            Instruction::LdhAddrA => format!("ldh [{}], a", address(0xFF00 | self.immediate)),
+            Instruction::LdhAAddr => format!("ldh a, [{}]", address(0xFF00 | self.immediate)),
+            Instruction::LdhCA => "ldh [c], a".to_string(),
+            Instruction::LdhAC => "ldh a, [c]".to_string(),
+            Instruction::LdAddrSp => format!("ld [{}], sp", n16),
+            Instruction::LdSpHl => "ld sp, hl".to_string(),
+            Instruction::LdHlSpOffset => format!("ld hl, sp{:+}", e8),
+
+            Instruction::Inc(target) => format!("inc {}", r8(target)),
+            Instruction::Dec(target) => format!("dec {}", r8(target)),
+            Instruction::IncRr(rr) => format!("inc {}", r16(rr)),
+            Instruction::DecRr(rr) => format!("dec {}", r16(rr)),
+            Instruction::AddHlRr(rr) => format!("add hl, {}", r16(rr)),
+            Instruction::AddSpOffset => format!("add sp, {}", e8),
+            Instruction::Alu(op, source) => format!("{} a, {}", alu(op), r8(source)),
+            Instruction::AluImm(op) => format!("{} a, {}", alu(op), n8),
+            Instruction::Rlca => "rlca".to_string(),
+            Instruction::Rrca => "rrca".to_string(),
+            Instruction::Rla => "rla".to_string(),
+            Instruction::Rra => "rra".to_string(),
+            Instruction::Daa => "daa".to_string(),
+            Instruction::Cpl => "cpl".to_string(),
+            Instruction::Scf => "scf".to_string(),
+            Instruction::Ccf => "ccf".to_string(),
+
+            Instruction::Jp(condition) => with_condition("jp", condition, &n16),
+            Instruction::JpHl => "jp hl".to_string(),
+            Instruction::Jr(condition) => {
+                // Relative to the address after the two-byte instruction
+                let target = self.address.wrapping_add(2).wrapping_add(e8 as u16);
+                with_condition("jr", condition, &address(target))
+            }
+            Instruction::Call(condition) => with_condition("call", condition, &n16),
+            Instruction::Ret(None) => "ret".to_string(),
+            Instruction::Ret(Some(condition)) => format!("ret {}", cc(condition)),
+            Instruction::Reti => "reti".to_string(),
+            Instruction::Rst(vector) => format!("rst ${:02X}", vector),
+            Instruction::Push(rr) => format!("push {}", r16(rr)),
+            Instruction::Pop(rr) => format!("pop {}", r16(rr)),
+
+            // Only seen when the CB prefix is the last byte available
+            Instruction::Prefix => "db $CB".to_string(),
+            Instruction::Cb(op, target) => match op {
+                CbOp::Bit(bit) => format!("bit {}, {}", bit, r8(target)),
+                CbOp::Res(bit) => format!("res {}, {}", bit, r8(target)),
+                CbOp::Set(bit) => format!("set {}, {}", bit, r8(target)),
+                op => format!("{} {}", cb(op), r8(target)),
+            },
+            Instruction::Illegal(opcode) => format!("db ${:02X}", opcode),
+        }
+    }
+}
+
+impl fmt::Display for Disassembled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(None))
+    }
+}
+
+/// Decode the instruction at `address` on any bus, reading with `peek` so
+/// nothing is disturbed and OAM DMA doesn't hide the code. Returns it with
+/// its length in bytes.
+pub fn disassemble<B: Bus>(bus: &B, address: u16) -> Result<(Disassembled, u16)> {
+    let mut bytes = [0u8; 3];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = bus.peek(address.wrapping_add(i as u16))?;
+    }
+    let disassembled = decode_bytes(&bytes, address);
+    Ok((disassembled, disassembled.length()))
+}
+
+/// Linear sweep of one ROM bank into a text listing, one instruction per line.
+/// Bank 0 is listed at 0x0000-0x3FFF, every other bank at 0x4000-0x7FFF.
+pub fn disassemble_bank(rom: &[u8], bank: usize, symbols: Option<&Symbols>) -> Result<String> {
+    let start = bank * ROM_BANK_SIZE;
+    let data = rom.get(start..start + ROM_BANK_SIZE).ok_or_else(|| {
+        Error::Memory(format!(
+            "ROM bank {} is out of range ({} bytes)",
+            bank,
+            rom.len()
+        ))
+    })?;
+    let base: u16 = if bank == 0 { 0x0000 } else { 0x4000 };
+
+    let mut listing = String::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let address = base + offset as u16;
+        if let Some(label) = symbols.and_then(|s| s.get(address)) {
+            writeln!(listing, "{}:", label).ok();
+        }
+
+        let mut line = decode_bytes(&data[offset..], address);
+        let mut length = line.length() as usize;
+        if offset + length > data.len() {
+            // Cut off by the end of the bank, show the opcode as data
+            line.instruction = Instruction::Illegal(data[offset]);
+            length = 1;
+        }
+
+        let raw: Vec<String> = data[offset..offset + length]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        writeln!(
+            listing,
+            "{:02X}:{:04X}  {:<8}  {}",
+            bank,
+            address,
+            raw.join(" "),
+            line.format(symbols)
+        )
+        .ok();
+        offset += length;
+    }
+    Ok(listing)
+}
+
+/// Decode from the bytes starting at `address`; missing operand bytes read as zero
+fn decode_bytes(bytes: &[u8], address: u16) -> Disassembled {
+    let byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    let mut instruction = decode(byte(0));
+    if instruction == Instruction::Prefix && bytes.len() > 1 {
+        instruction = decode_cb(byte(1));
+    }
+
+    let immediate = match instruction {
+        Instruction::Cb(..) | Instruction::Prefix => 0,
+        _ => match instruction.length() {
+            2 => byte(1) as u16,
+            3 => u16::from_le_bytes([byte(1), byte(2)]),
+            _ => 0,
+        },
+    };
+
+    Disassembled {
+        address,
+        instruction,
+        immediate,
+    }
+}
+
+fn r8(target: RegTarget) -> &'static str {
+    match target {
+        RegTarget::A => "a",
+        RegTarget::B => "b",
+        RegTarget::C => "c",
+        RegTarget::D => "d",
+        RegTarget::E => "e",
+        RegTarget::H => "h",
+        RegTarget::L => "l",
+        _ => "[hl]",
+    }
+}
+
+fn r16(target: RegTarget) -> &'static str {
+    match target {
+        RegTarget::BC => "bc",
+        RegTarget::DE => "de",
+        RegTarget::HL => "hl",
+        RegTarget::AF => "af",
+        _ => "sp",
+    }
+}
+
+fn indirect(rr: Indirect) -> &'static str {
+    match rr {
+        Indirect::BC => "bc",
+        Indirect::DE => "de",
+        Indirect::HLI => "hl+",
+        Indirect::HLD => "hl-",
+    }
+}
+
+fn cc(condition: Condition) -> &'static str {
+    match condition {
+        Condition::NZ => "nz",
+        Condition::Z => "z",
+        Condition::NC => "nc",
+        Condition::C => "c",
+    }
+}
+
+fn with_condition(mnemonic: &str, condition: Option<Condition>, target: &str) -> String {
+    match condition {
+        Some(condition) => format!("{} {}, {}", mnemonic, cc(condition), target),
+        None => format!("{} {}", mnemonic, target),
+    }
+}
+
+fn alu(op: AluOp) -> &'static str {
+    match op {
+        AluOp::Add => "add",
+        AluOp::Adc => "adc",
+        AluOp::Sub => "sub",
+        AluOp::Sbc => "sbc",
+        AluOp::And => "and",
+        AluOp::Xor => "xor",
+        AluOp::Or => "or",
+        AluOp::Cp => "cp",
+    }
+}
+
+fn cb(op: CbOp) -> &'static str {
+    match op {
+        CbOp::Rlc => "rlc",
+        CbOp::Rrc => "rrc",
+        CbOp::Rl => "rl",
+        CbOp::Rr => "rr",
+        CbOp::Sla => "sla",
+        CbOp::Sra => "sra",
+        CbOp::Swap => "swap",
+        CbOp::Srl => "srl",
+        CbOp::Bit(_) => "bit",
+        CbOp::Res(_) => "res",
+        CbOp::Set(_) => "set",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::bus::FlatBus;
+    use crate::core::mmu::MMU;
+
+    #[test]
+    fn test_disassemble_resolves_operands() {
+        let mut mmu = MMU::new();
+        let program = [
+            0x20, 0xFE, // jr nz, $C000
+            0xE0, 0x44, // ldh [$FF44], a
+            0xCB, 0x7E, // bit 7, [hl]
+            0xCD, 0x50, 0x01, // call Main
+            0xF8, 0xFD, // ld hl, sp-3
+            0xD3, // db $D3
+        ];
+        for (i, &byte) in program.iter().enumerate() {
+            mmu.write_byte(0xC000 + i as u16, byte).unwrap();
+        }
+        let mut symbols = Symbols::new();
+        symbols.insert(0x0150, "Main");
+
+        let mut address = 0xC000;
+        let mut lines = Vec::new();
+        for _ in 0..6 {
+            let (line, length) = disassemble(&mmu, address).unwrap();
+            lines.push(line.format(Some(&symbols)));
+            address += length;
+        }
+        assert_eq!(
+            lines,
+            [
+                "jr nz, $C000",
+                "ldh [$FF44], a",
+                "bit 7, [hl]",
+                "call Main",
+                "ld hl, sp-3",
+                "db $D3"
+            ]
+        );
+
+        // Any bus works, and an OAM DMA in progress doesn't hide the code
+        let mut bus = FlatBus::new();
+        bus.memory_mut()[0xC000..0xC002].copy_from_slice(&[0x20, 0xFE]);
+        let (line, _) = disassemble(&bus, 0xC000).unwrap();
+        assert_eq!(line.to_string(), "jr nz, $C000");
+        mmu.write_byte(0xFF46, 0xC0).unwrap();
+        mmu.step(4 * 2);
+        assert_eq!(mmu.read_byte(0xC000).unwrap(), 0xFF);
+        let (line, _) = disassemble(&mmu, 0xC000).unwrap();
+        assert_eq!(line.to_string(), "jr nz, $C000");
+    }
+
+    #[test]
+    fn test_bank_listing_labels_and_truncation() {
+        let mut rom = vec![0u8; 2 * ROM_BANK_SIZE];
+        rom[0x4000] = 0xC3; // jp $4000
+        rom[0x4001] = 0x00;
+        rom[0x4002] = 0x40;
+        rom[0x7FFF] = 0x01; // ld bc, n16 cut off by the bank end
+
+        let symbols = Symbols::parse_sym("00:4000 Other\n01:4000 Loop ; bank 1\n", 1);
+        let listing = disassemble_bank(&rom, 1, Some(&symbols)).unwrap();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines[0], "Loop:");
+        assert_eq!(lines[1], "01:4000  C3 00 40  jp Loop");
+        assert_eq!(*lines.last().unwrap(), "01:7FFF  01        db $01");
+        assert!(disassemble_bank(&rom, 2, None).is_err());
+    }
+}
@@ -1,50 +1,165 @@
 // arithmetic.rs - CPU 算術運算指令
 // 2025.06.21
 
-use crate::core::{cpu::CPU, cycles::CyclesType};
+use super::register_utils::FlagOperations;
+use crate::core::bus::Bus;
+use crate::core::cpu::CPU;
 use crate::error::{Error, InstructionError, RegTarget, Result};
 
-/// 算術運算指令分派
-pub fn dispatch(cpu: &mut CPU, opcode: u8) -> Result<CyclesType> {
-    match opcode {
-        // DEC r 指令族 (05, 0D, 15, 1D, 25, 2D, 35, 3D)
-        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D => {
-            let reg = (opcode >> 3) & 0x07;
-            let target = RegTarget::from_bits(reg)?;
-            cpu.dec_r(target)
-        }
+impl<B: Bus> CPU<B> {
+    pub fn inc_r(&mut self, target: RegTarget) -> Result<()> {
+        let value = match target {
+            RegTarget::A => self.registers.a,
+            RegTarget::B => self.registers.b,
+            RegTarget::C => self.registers.c,
+            RegTarget::D => self.registers.d,
+            RegTarget::E => self.registers.e,
+            RegTarget::H => self.registers.h,
+            RegTarget::L => self.registers.l,
+            RegTarget::HL => {
+                let addr = self.registers.get_hl();
+                self.read_byte(addr)?
+            }
+            _ => {
+                return Err(Error::Instruction(InstructionError::InvalidRegister(
+                    target,
+                )))
+            }
+        };
+
+        // C 標誌不受影響
+        let result = value.wrapping_add(1);
+        self.registers.set_zero(result == 0);
+        self.registers.set_subtract(false);
+        self.registers.set_half_carry((result & 0x0F) == 0);
 
-        // ADD/ADC/SUB/SBC 指令族 (0x80-0x9F)
-        0x80..=0x9F => {
-            let src = (opcode & 0x07) as u8;
-            let source = RegTarget::from_bits(src)?;
-            let use_carry = (opcode & 0x18) == 0x18 || (opcode & 0x18) == 0x08;
-            match opcode & 0xF0 {
-                0x80 => cpu.add_a_r(source, false),
-                0x90 => cpu.sub_a_r(source, false),
-                _ => {
-                    if use_carry {
-                        if (opcode & 0xF0) == 0x80 {
-                            cpu.add_a_r(source, true)
-                        } else {
-                            cpu.sub_a_r(source, true)
-                        }
-                    } else {
-                        Err(Error::Instruction(InstructionError::InvalidOpcode(opcode)))
-                    }
-                }
+        match target {
+            RegTarget::A => self.registers.a = result,
+            RegTarget::B => self.registers.b = result,
+            RegTarget::C => self.registers.c = result,
+            RegTarget::D => self.registers.d = result,
+            RegTarget::E => self.registers.e = result,
+            RegTarget::H => self.registers.h = result,
+            RegTarget::L => self.registers.l = result,
+            _ => {
+                let addr = self.registers.get_hl();
+                self.write_byte(addr, result)?;
             }
         }
+        Ok(())
+    }
+
+    /// 讀取 16 位元暫存器對 (BC, DE, HL, SP)
+    fn get_rr(&self, rr: RegTarget) -> Result<u16> {
+        Ok(match rr {
+            RegTarget::BC => self.registers.get_bc(),
+            RegTarget::DE => self.registers.get_de(),
+            RegTarget::HL => self.registers.get_hl(),
+            RegTarget::SP => self.registers.get_sp(),
+            _ => return Err(Error::Instruction(InstructionError::InvalidRegister(rr))),
+        })
+    }
+
+    fn set_rr(&mut self, rr: RegTarget, value: u16) -> Result<()> {
+        match rr {
+            RegTarget::BC => self.registers.set_bc(value),
+            RegTarget::DE => self.registers.set_de(value),
+            RegTarget::HL => self.registers.set_hl(value),
+            RegTarget::SP => self.registers.set_sp(value),
+            _ => return Err(Error::Instruction(InstructionError::InvalidRegister(rr))),
+        }
+        Ok(())
+    }
+
+    pub fn inc_rr(&mut self, rr: RegTarget) -> Result<()> {
+        let value = self.get_rr(rr)?.wrapping_add(1);
+        self.set_rr(rr, value)?;
+        Ok(())
+    }
 
-        // 立即數運算指令 (0xC6, 0xCE, 0xD6, 0xDE)
-        0xC6 | 0xCE | 0xD6 | 0xDE => {
-            let use_carry = opcode == 0xCE || opcode == 0xDE;
-            match opcode {
-                0xC6 | 0xCE => cpu.add_a_n(use_carry),
-                0xD6 | 0xDE => cpu.sub_a_n(use_carry),
-                _ => unreachable!(),
+    pub fn dec_rr(&mut self, rr: RegTarget) -> Result<()> {
+        let value = self.get_rr(rr)?.wrapping_sub(1);
+        self.set_rr(rr, value)?;
+        Ok(())
+    }
+
+    pub fn add_hl_rr(&mut self, rr: RegTarget) -> Result<()> {
+        let hl = self.registers.get_hl();
+        let value = self.get_rr(rr)?;
+        let (result, carry) = hl.overflowing_add(value);
+        let half_carry = (hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF;
+
+        // Z 標誌不受影響，H 為 bit 11 進位
+        self.registers.set_subtract(false);
+        self.registers.set_half_carry(half_carry);
+        self.registers.set_carry(carry);
+        self.registers.set_hl(result);
+        Ok(())
+    }
+
+    pub fn add_sp_e(&mut self) -> Result<()> {
+        let sp = self.registers.get_sp();
+        let offset = self.fetch_byte()? as i8 as i16 as u16;
+
+        // H 與 C 依低位元組的無號加法計算，與 LD HL, SP+e 相同
+        let half_carry = (sp & 0x0F) + (offset & 0x0F) > 0x0F;
+        let carry = (sp & 0xFF) + (offset & 0xFF) > 0xFF;
+        self.registers.set_zero(false);
+        self.registers.set_subtract(false);
+        self.registers.set_half_carry(half_carry);
+        self.registers.set_carry(carry);
+        self.registers.set_sp(sp.wrapping_add(offset));
+        Ok(())
+    }
+
+    /// 依前一次運算的 N/H/C 標誌把 A 調整為 BCD
+    pub fn daa(&mut self) -> Result<()> {
+        let mut a = self.registers.a;
+        let mut carry = self.registers.get_carry();
+
+        if self.registers.get_subtract() {
+            if carry {
+                a = a.wrapping_sub(0x60);
+            }
+            if self.registers.get_half_carry() {
+                a = a.wrapping_sub(0x06);
+            }
+        } else {
+            if carry || a > 0x99 {
+                a = a.wrapping_add(0x60);
+                carry = true;
+            }
+            if self.registers.get_half_carry() || (a & 0x0F) > 0x09 {
+                a = a.wrapping_add(0x06);
             }
         }
-        _ => Err(Error::Instruction(InstructionError::InvalidOpcode(opcode))),
+
+        self.registers.a = a;
+        self.registers.set_zero(a == 0);
+        self.registers.set_half_carry(false);
+        self.registers.set_carry(carry);
+        Ok(())
+    }
+
+    pub fn cpl(&mut self) -> Result<()> {
+        self.registers.a = !self.registers.a;
+        self.registers.set_subtract(true);
+        self.registers.set_half_carry(true);
+        Ok(())
+    }
+
+    pub fn scf(&mut self) -> Result<()> {
+        self.registers.set_subtract(false);
+        self.registers.set_half_carry(false);
+        self.registers.set_carry(true);
+        Ok(())
+    }
+
+    pub fn ccf(&mut self) -> Result<()> {
+        let carry = self.registers.get_carry();
+        self.registers.set_subtract(false);
+        self.registers.set_half_carry(false);
+        self.registers.set_carry(!carry);
+        Ok(())
     }
 }
@@ -0,0 +1,380 @@
+// decode.rs - 操作碼解碼表
+// 主表與 CB 表各 256 項，於編譯期產生，執行、反組譯與追蹤共用
+
+use crate::core::cycles::{CyclesType, CYCLES_1, CYCLES_2, CYCLES_3, CYCLES_4, CYCLES_5, CYCLES_6};
+use crate::error::RegTarget;
+
+/// 跳躍、呼叫與返回的條件碼
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    NZ,
+    Z,
+    NC,
+    C,
+}
+
+/// 累加器的 8 位元 ALU 運算 (0x80-0xBF 及立即數版本)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp,
+}
+
+/// CB 前綴的旋轉、位移與位元運算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CbOp {
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl,
+    Bit(u8),
+    Res(u8),
+    Set(u8),
+}
+
+/// 透過 16 位元暫存器存取記憶體的 LD 形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indirect {
+    BC,
+    DE,
+    HLI, // (HL+)
+    HLD, // (HL-)
+}
+
+/// 解碼後的指令
+///
+/// 8 位元運算元以 `RegTarget` 表示，`RegTarget::HL` 代表 (HL)；
+/// 立即數不在表中，執行時由 PC 讀取，長度見 `length()`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,
+    Stop,
+    Halt,
+    Di,
+    Ei,
+
+    Ld(RegTarget, RegTarget), // LD r, r'
+    LdImm(RegTarget),         // LD r, n
+    LdRrImm(RegTarget),       // LD rr, nn
+    LdIndirectA(Indirect),    // LD (rr), A
+    LdAIndirect(Indirect),    // LD A, (rr)
+    LdAddrA,                  // LD (nn), A
+    LdAAddr,                  // LD A, (nn)
+    LdhAddrA,                 // LDH (n), A
+    LdhAAddr,                 // LDH A, (n)
+    LdhCA,                    // LD (C), A
+    LdhAC,                    // LD A, (C)
+    LdAddrSp,                 // LD (nn), SP
+    LdSpHl,                   // LD SP, HL
+    LdHlSpOffset,             // LD HL, SP+e
+
+    Inc(RegTarget),
+    Dec(RegTarget),
+    IncRr(RegTarget),
+    DecRr(RegTarget),
+    AddHlRr(RegTarget),
+    AddSpOffset, // ADD SP, e
+    Alu(AluOp, RegTarget),
+    AluImm(AluOp),
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+
+    Jp(Option<Condition>),
+    JpHl,
+    Jr(Option<Condition>),
+    Call(Option<Condition>),
+    Ret(Option<Condition>),
+    Reti,
+    Rst(u8),
+    Push(RegTarget),
+    Pop(RegTarget),
+
+    Prefix, // 0xCB，實際指令在 CB 表
+    Cb(CbOp, RegTarget),
+    Illegal(u8),
+}
+
+impl Instruction {
+    /// 指令長度 (位元組)，包含操作碼與 CB 前綴
+    pub fn length(&self) -> u16 {
+        use Instruction::*;
+        match self {
+            Stop | LdImm(_) | LdhAddrA | LdhAAddr | LdHlSpOffset | AddSpOffset | AluImm(_)
+            | Jr(_) | Prefix | Cb(..) => 2,
+            LdRrImm(_) | LdAddrA | LdAAddr | LdAddrSp | Jp(_) | Call(_) => 3,
+            _ => 1,
+        }
+    }
+
+    /// 不跳躍時的週期數 (T-cycles)
+    pub fn cycles(&self) -> CyclesType {
+        use Instruction::*;
+        match self {
+            Ld(RegTarget::HL, _) | Ld(_, RegTarget::HL) => CYCLES_2,
+            LdImm(RegTarget::HL) => CYCLES_3,
+            LdImm(_) => CYCLES_2,
+            LdRrImm(_) => CYCLES_3,
+            LdIndirectA(_) | LdAIndirect(_) => CYCLES_2,
+            LdAddrA | LdAAddr => CYCLES_4,
+            LdhAddrA | LdhAAddr => CYCLES_3,
+            LdhCA | LdhAC => CYCLES_2,
+            LdAddrSp => CYCLES_5,
+            LdSpHl => CYCLES_2,
+            LdHlSpOffset => CYCLES_3,
+
+            Inc(RegTarget::HL) | Dec(RegTarget::HL) => CYCLES_3,
+            IncRr(_) | DecRr(_) | AddHlRr(_) => CYCLES_2,
+            AddSpOffset => CYCLES_4,
+            Alu(_, RegTarget::HL) | AluImm(_) => CYCLES_2,
+
+            Jp(None) => CYCLES_4,
+            Jp(Some(_)) => CYCLES_3,
+            Jr(None) => CYCLES_3,
+            Jr(Some(_)) => CYCLES_2,
+            Call(None) => CYCLES_6,
+            Call(Some(_)) => CYCLES_3,
+            Ret(None) | Reti => CYCLES_4,
+            Ret(Some(_)) => CYCLES_2,
+            Rst(_) | Push(_) => CYCLES_4,
+            Pop(_) => CYCLES_3,
+
+            Cb(CbOp::Bit(_), RegTarget::HL) => CYCLES_3,
+            Cb(_, RegTarget::HL) => CYCLES_4,
+            Cb(..) => CYCLES_2,
+
+            _ => CYCLES_1,
+        }
+    }
+
+    /// 條件成立時的週期數，無條件指令為 `None`
+    pub fn branch_cycles(&self) -> Option<CyclesType> {
+        match self {
+            Instruction::Jp(Some(_)) => Some(CYCLES_4),
+            Instruction::Jr(Some(_)) => Some(CYCLES_3),
+            Instruction::Call(Some(_)) => Some(CYCLES_6),
+            Instruction::Ret(Some(_)) => Some(CYCLES_5),
+            _ => None,
+        }
+    }
+}
+
+/// 主操作碼表
+pub static OPCODES: [Instruction; 256] = build_table(false);
+/// CB 前綴操作碼表
+pub static CB_OPCODES: [Instruction; 256] = build_table(true);
+
+/// 解碼主操作碼
+pub fn decode(opcode: u8) -> Instruction {
+    OPCODES[opcode as usize]
+}
+
+/// 解碼 CB 前綴後的操作碼
+pub fn decode_cb(opcode: u8) -> Instruction {
+    CB_OPCODES[opcode as usize]
+}
+
+const fn build_table(cb: bool) -> [Instruction; 256] {
+    let mut table = [Instruction::Nop; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = if cb {
+            decode_prefixed(i as u8)
+        } else {
+            decode_unprefixed(i as u8)
+        };
+        i += 1;
+    }
+    table
+}
+
+/// 8 位元運算元編碼 (B, C, D, E, H, L, (HL), A)
+const fn r8(bits: u8) -> RegTarget {
+    match bits & 0x07 {
+        0 => RegTarget::B,
+        1 => RegTarget::C,
+        2 => RegTarget::D,
+        3 => RegTarget::E,
+        4 => RegTarget::H,
+        5 => RegTarget::L,
+        6 => RegTarget::HL,
+        _ => RegTarget::A,
+    }
+}
+
+/// 16 位元暫存器對編碼，`stack` 為 PUSH/POP 時第 3 組是 AF 而非 SP
+const fn r16(bits: u8, stack: bool) -> RegTarget {
+    match bits & 0x03 {
+        0 => RegTarget::BC,
+        1 => RegTarget::DE,
+        2 => RegTarget::HL,
+        _ if stack => RegTarget::AF,
+        _ => RegTarget::SP,
+    }
+}
+
+const fn condition(bits: u8) -> Condition {
+    match bits & 0x03 {
+        0 => Condition::NZ,
+        1 => Condition::Z,
+        2 => Condition::NC,
+        _ => Condition::C,
+    }
+}
+
+const fn alu_op(bits: u8) -> AluOp {
+    match bits & 0x07 {
+        0 => AluOp::Add,
+        1 => AluOp::Adc,
+        2 => AluOp::Sub,
+        3 => AluOp::Sbc,
+        4 => AluOp::And,
+        5 => AluOp::Xor,
+        6 => AluOp::Or,
+        _ => AluOp::Cp,
+    }
+}
+
+const fn indirect(bits: u8) -> Indirect {
+    match bits & 0x03 {
+        0 => Indirect::BC,
+        1 => Indirect::DE,
+        2 => Indirect::HLI,
+        _ => Indirect::HLD,
+    }
+}
+
+const fn decode_unprefixed(opcode: u8) -> Instruction {
+    use Instruction::*;
+    let x = opcode >> 6;
+    let y = (opcode >> 3) & 0x07;
+    let z = opcode & 0x07;
+    let p = y >> 1;
+
+    match opcode {
+        0x00 => Nop,
+        0x10 => Stop,
+        0x76 => Halt, // 佔用 LD (HL), (HL) 的編碼
+        0xF3 => Di,
+        0xFB => Ei,
+        0x08 => LdAddrSp,
+        0x18 => Jr(None),
+        0x20 | 0x28 | 0x30 | 0x38 => Jr(Some(condition(y))),
+        0x07 => Rlca,
+        0x0F => Rrca,
+        0x17 => Rla,
+        0x1F => Rra,
+        0x27 => Daa,
+        0x2F => Cpl,
+        0x37 => Scf,
+        0x3F => Ccf,
+
+        0xC3 => Jp(None),
+        0xE9 => JpHl,
+        0xCD => Call(None),
+        0xC9 => Ret(None),
+        0xD9 => Reti,
+        0xCB => Prefix,
+        0xE0 => LdhAddrA,
+        0xF0 => LdhAAddr,
+        0xE2 => LdhCA,
+        0xF2 => LdhAC,
+        0xEA => LdAddrA,
+        0xFA => LdAAddr,
+        0xE8 => AddSpOffset,
+        0xF8 => LdHlSpOffset,
+        0xF9 => LdSpHl,
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => Ret(Some(condition(y))),
+        0xC2 | 0xCA | 0xD2 | 0xDA => Jp(Some(condition(y))),
+        0xC4 | 0xCC | 0xD4 | 0xDC => Call(Some(condition(y))),
+
+        _ => match (x, z) {
+            (0, 1) if y & 1 == 0 => LdRrImm(r16(p, false)),
+            (0, 1) => AddHlRr(r16(p, false)),
+            (0, 2) if y & 1 == 0 => LdIndirectA(indirect(p)),
+            (0, 2) => LdAIndirect(indirect(p)),
+            (0, 3) if y & 1 == 0 => IncRr(r16(p, false)),
+            (0, 3) => DecRr(r16(p, false)),
+            (0, 4) => Inc(r8(y)),
+            (0, 5) => Dec(r8(y)),
+            (0, 6) => LdImm(r8(y)),
+            (1, _) => Ld(r8(y), r8(z)),
+            (2, _) => Alu(alu_op(y), r8(z)),
+            (3, 1) if y & 1 == 0 => Pop(r16(p, true)),
+            (3, 5) if y & 1 == 0 => Push(r16(p, true)),
+            (3, 6) => AluImm(alu_op(y)),
+            (3, 7) => Rst(y * 8),
+            // 0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD
+            _ => Illegal(opcode),
+        },
+    }
+}
+
+const fn decode_prefixed(opcode: u8) -> Instruction {
+    let y = (opcode >> 3) & 0x07;
+    let op = match opcode >> 6 {
+        0 => match y {
+            0 => CbOp::Rlc,
+            1 => CbOp::Rrc,
+            2 => CbOp::Rl,
+            3 => CbOp::Rr,
+            4 => CbOp::Sla,
+            5 => CbOp::Sra,
+            6 => CbOp::Swap,
+            _ => CbOp::Srl,
+        },
+        1 => CbOp::Bit(y),
+        2 => CbOp::Res(y),
+        _ => CbOp::Set(y),
+    };
+    Instruction::Cb(op, r8(opcode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_tables() {
+        assert_eq!(decode(0x46), Instruction::Ld(RegTarget::B, RegTarget::HL));
+        assert_eq!(decode(0x46).cycles(), CYCLES_2);
+        assert_eq!(decode(0xF5), Instruction::Push(RegTarget::AF));
+        assert_eq!(decode(0x39), Instruction::AddHlRr(RegTarget::SP));
+        assert_eq!(decode(0x3A), Instruction::LdAIndirect(Indirect::HLD));
+
+        let call_nz = decode(0xC4);
+        assert_eq!(call_nz, Instruction::Call(Some(Condition::NZ)));
+        assert_eq!(call_nz.length(), 3);
+        assert_eq!(call_nz.cycles(), CYCLES_3);
+        assert_eq!(call_nz.branch_cycles(), Some(CYCLES_6));
+
+        assert_eq!(
+            decode_cb(0x7E),
+            Instruction::Cb(CbOp::Bit(7), RegTarget::HL)
+        );
+        assert_eq!(decode_cb(0x7E).cycles(), CYCLES_3);
+        assert_eq!(decode_cb(0x36).cycles(), CYCLES_4);
+
+        // 11 個未定義的操作碼
+        let illegal = OPCODES
+            .iter()
+            .filter(|i| matches!(i, Instruction::Illegal(_)))
+            .count();
+        assert_eq!(illegal, 11);
+    }
+}
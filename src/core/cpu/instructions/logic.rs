@@ -1,56 +1,10 @@
+use crate::core::bus::Bus;
 use crate::core::cpu::instructions::register_utils::FlagOperations;
 use crate::core::cpu::CPU;
-use crate::core::cycles::{CyclesType, CYCLES_1, CYCLES_2};
 use crate::error::{Error, InstructionError, RegTarget, Result};
 
-pub fn dispatch(cpu: &mut CPU, opcode: u8) -> Result<CyclesType> {
-    match opcode {
-        // AND r
-        0xA0..=0xA7 => {
-            let reg = opcode & 0x07;
-            let target = RegTarget::from_bits(reg)?;
-            cpu.and_a_r(target)
-        }
-
-        // AND n
-        0xE6 => cpu.and_a_n(),
-
-        // OR r
-        0xB0..=0xB7 => {
-            let reg = opcode & 0x07;
-            let target = RegTarget::from_bits(reg)?;
-            cpu.or_a_r(target)
-        }
-
-        // OR n
-        0xF6 => cpu.or_a_n(),
-
-        // XOR r
-        0xA8..=0xAF => {
-            let reg = opcode & 0x07;
-            let target = RegTarget::from_bits(reg)?;
-            cpu.xor_a_r(target)
-        }
-
-        // XOR n
-        0xEE => cpu.xor_a_n(),
-
-        // CP r
-        0xB8..=0xBF => {
-            let reg = opcode & 0x07;
-            let target = RegTarget::from_bits(reg)?;
-            cpu.cp_a_r(target)
-        }
-
-        // CP n
-        0xFE => cpu.cp_a_n(),
-
-        _ => Err(Error::Instruction(InstructionError::InvalidOpcode(opcode))),
-    }
-}
-
-impl CPU {
-    pub fn and_a_r(&mut self, reg: RegTarget) -> Result<CyclesType> {
+impl<B: Bus> CPU<B> {
+    pub fn and_a_r(&mut self, reg: RegTarget) -> Result<()> {
         let value = match reg {
             RegTarget::A => self.registers.a,
             RegTarget::B => self.registers.b,
@@ -68,21 +22,17 @@ impl CPU {
 
         self.registers.a &= value;
         self.update_logic_flags(self.registers.a, true);
-        Ok(if matches!(reg, RegTarget::HL) {
-            CYCLES_2
-        } else {
-            CYCLES_1
-        })
+        Ok(())
     }
 
-    pub fn and_a_n(&mut self) -> Result<CyclesType> {
+    pub fn and_a_n(&mut self) -> Result<()> {
         let value = self.fetch_byte()?;
         self.registers.a &= value;
         self.update_logic_flags(self.registers.a, true);
-        Ok(CYCLES_2)
+        Ok(())
     }
 
-    pub fn or_a_r(&mut self, reg: RegTarget) -> Result<CyclesType> {
+    pub fn or_a_r(&mut self, reg: RegTarget) -> Result<()> {
         let value = match reg {
             RegTarget::A => self.registers.a,
             RegTarget::B => self.registers.b,
@@ -100,21 +50,17 @@ impl CPU {
 
         self.registers.a |= value;
         self.update_logic_flags(self.registers.a, false);
-        Ok(if matches!(reg, RegTarget::HL) {
-            CYCLES_2
-        } else {
-            CYCLES_1
-        })
+        Ok(())
     }
 
-    pub fn or_a_n(&mut self) -> Result<CyclesType> {
+    pub fn or_a_n(&mut self) -> Result<()> {
         let value = self.fetch_byte()?;
         self.registers.a |= value;
         self.update_logic_flags(self.registers.a, false);
-        Ok(CYCLES_2)
+        Ok(())
     }
 
-    pub fn xor_a_r(&mut self, reg: RegTarget) -> Result<CyclesType> {
+    pub fn xor_a_r(&mut self, reg: RegTarget) -> Result<()> {
         let value = match reg {
             RegTarget::A => self.registers.a,
             RegTarget::B => self.registers.b,
@@ -132,21 +78,17 @@ impl CPU {
 
         self.registers.a ^= value;
         self.update_logic_flags(self.registers.a, false);
-        Ok(if matches!(reg, RegTarget::HL) {
-            CYCLES_2
-        } else {
-            CYCLES_1
-        })
+        Ok(())
     }
 
-    pub fn xor_a_n(&mut self) -> Result<CyclesType> {
+    pub fn xor_a_n(&mut self) -> Result<()> {
         let value = self.fetch_byte()?;
         self.registers.a ^= value;
         self.update_logic_flags(self.registers.a, false);
-        Ok(CYCLES_2)
+        Ok(())
     }
 
-    pub fn cp_a_r(&mut self, reg: RegTarget) -> Result<CyclesType> {
+    pub fn cp_a_r(&mut self, reg: RegTarget) -> Result<()> {
         let value = match reg {
             RegTarget::A => self.registers.a,
             RegTarget::B => self.registers.b,
@@ -163,17 +105,13 @@ impl CPU {
         };
 
         self.cp_a(value);
-        Ok(if matches!(reg, RegTarget::HL) {
-            CYCLES_2
-        } else {
-            CYCLES_1
-        })
+        Ok(())
     }
 
-    pub fn cp_a_n(&mut self) -> Result<CyclesType> {
+    pub fn cp_a_n(&mut self) -> Result<()> {
         let value = self.fetch_byte()?;
         self.cp_a(value);
-        Ok(CYCLES_2)
+        Ok(())
     }
 
     fn cp_a(&mut self, value: u8) {
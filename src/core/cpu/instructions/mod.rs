@@ -1,74 +1,238 @@
 pub mod arithmetic;
 pub mod bit;
 pub mod control;
-pub mod cycles;
+pub mod decode;
 pub mod jump;
 pub mod load;
 pub mod logic;
 pub mod prelude;
 pub mod register_utils;
 
-use crate::error::{Error, InstructionError, Result};
+use self::decode::{AluOp, CbOp, Indirect, Instruction};
+use crate::core::bus::Bus;
+use crate::core::cpu::CPU;
+use crate::core::cycles::CyclesType;
+use crate::error::{RegTarget, Result};
 
-pub fn execute(
-    cpu: &mut crate::core::cpu::CPU,
-    opcode: u8,
-) -> Result<crate::core::cycles::CyclesType> {
-    // 非法操作碼判斷
-    if [
-        0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
-    ]
-    .contains(&opcode)
-    {
-        return Err(Error::Instruction(InstructionError::InvalidOpcode(opcode)));
-    }
+/// 執行已解碼的指令，回傳實際花費的週期數
+///
+/// 立即數在此時才由 PC 讀取；週期數一律取自解碼表，條件成立時使用 `branch_cycles()`。
+pub fn execute<B: Bus>(cpu: &mut CPU<B>, instruction: Instruction) -> Result<CyclesType> {
+    let mut taken = false;
 
-    match opcode {
-        // NOP
-        0x00 => Ok(4),
+    match instruction {
+        Instruction::Nop => {}
+        Instruction::Stop => {
+            cpu.stop()?;
+        }
+        Instruction::Halt => {
+            cpu.halt()?;
+        }
+        Instruction::Di => {
+            cpu.disable_interrupts()?;
+        }
+        Instruction::Ei => {
+            cpu.enable_interrupts()?;
+        }
 
         // LD 指令
-        0x08 => load::dispatch(cpu, opcode), // LD (nn),SP
-        0x01 | 0x11 | 0x21 | 0x31 => load::dispatch(cpu, opcode), // LD rr,nn
-        0x02 | 0x12 | 0x22 | 0x32 => load::dispatch(cpu, opcode), // LD (rr),A
-        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => load::dispatch(cpu, opcode), // LD r,n
-        0x0A | 0x1A | 0x2A | 0x3A => load::dispatch(cpu, opcode), // LD A,(rr)
-        0x40..=0x75 | 0x77..=0x7F => load::dispatch(cpu, opcode), // LD r,r
-
-        // INC/DEC 指令
-        0x03 | 0x13 | 0x23 | 0x33 => arithmetic::dispatch(cpu, opcode), // INC rr
-        0x0B | 0x1B | 0x2B | 0x3B => arithmetic::dispatch(cpu, opcode), // DEC rr
-        0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C => arithmetic::dispatch(cpu, opcode), // INC r
-        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D => arithmetic::dispatch(cpu, opcode), // DEC r
-
-        // 控制指令
-        0x10 => control::dispatch(cpu, opcode), // STOP
-        0x76 => control::dispatch(cpu, opcode), // HALT
+        Instruction::Ld(target, source) => {
+            cpu.ld_r_r(target, source)?;
+        }
+        Instruction::LdImm(RegTarget::HL) => {
+            cpu.ld_hl_n()?;
+        }
+        Instruction::LdImm(target) => {
+            cpu.ld_r_n(target)?;
+        }
+        Instruction::LdRrImm(rr) => {
+            match rr {
+                RegTarget::BC => cpu.ld_bc_nn(),
+                RegTarget::DE => cpu.ld_de_nn(),
+                RegTarget::HL => cpu.ld_hl_nn(),
+                _ => cpu.ld_sp_nn(),
+            }?;
+        }
+        Instruction::LdIndirectA(rr) => {
+            match rr {
+                Indirect::BC => cpu.ld_bc_a(),
+                Indirect::DE => cpu.ld_de_a(),
+                Indirect::HLI => cpu.ld_hli_a(),
+                Indirect::HLD => cpu.ld_hld_a(),
+            }?;
+        }
+        Instruction::LdAIndirect(rr) => {
+            match rr {
+                Indirect::BC => cpu.ld_a_bc(),
+                Indirect::DE => cpu.ld_a_de(),
+                Indirect::HLI => cpu.ld_a_hli(),
+                Indirect::HLD => cpu.ld_a_hld(),
+            }?;
+        }
+        Instruction::LdAddrA => {
+            cpu.ld_nn_a()?;
+        }
+        Instruction::LdAAddr => {
+            cpu.ld_a_nn()?;
+        }
+        Instruction::LdhAddrA => {
+            cpu.ldh_n_a()?;
+        }
+        Instruction::LdhAAddr => {
+            cpu.ldh_a_n()?;
+        }
+        Instruction::LdhCA => {
+            cpu.ld_c_a()?;
+        }
+        Instruction::LdhAC => {
+            cpu.ld_a_c()?;
+        }
+        Instruction::LdAddrSp => {
+            cpu.ld_nn_sp()?;
+        }
+        Instruction::LdSpHl => {
+            cpu.ld_sp_hl()?;
+        }
+        Instruction::LdHlSpOffset => {
+            cpu.ld_hl_sp_r8()?;
+        }
 
-        // 跳躍指令
-        0x18 => jump::dispatch(cpu, opcode), // JR r8
-        0x20 | 0x28 | 0x30 | 0x38 => jump::dispatch(cpu, opcode), // JR cc,r8
-        0xC2 | 0xC3 | 0xCA | 0xD2 | 0xDA | 0xE9 => jump::dispatch(cpu, opcode), // JP
+        // 算術與邏輯運算
+        Instruction::Inc(target) => {
+            cpu.inc_r(target)?;
+        }
+        Instruction::Dec(target) => {
+            cpu.dec_r(target)?;
+        }
+        Instruction::IncRr(rr) => {
+            cpu.inc_rr(rr)?;
+        }
+        Instruction::DecRr(rr) => {
+            cpu.dec_rr(rr)?;
+        }
+        Instruction::AddHlRr(rr) => {
+            cpu.add_hl_rr(rr)?;
+        }
+        Instruction::AddSpOffset => {
+            cpu.add_sp_e()?;
+        }
+        Instruction::Alu(op, source) => {
+            match op {
+                AluOp::Add => cpu.add_a_r(source, false),
+                AluOp::Adc => cpu.add_a_r(source, true),
+                AluOp::Sub => cpu.sub_a_r(source, false),
+                AluOp::Sbc => cpu.sub_a_r(source, true),
+                AluOp::And => cpu.and_a_r(source),
+                AluOp::Xor => cpu.xor_a_r(source),
+                AluOp::Or => cpu.or_a_r(source),
+                AluOp::Cp => cpu.cp_a_r(source),
+            }?;
+        }
+        Instruction::AluImm(op) => {
+            match op {
+                AluOp::Add => cpu.add_a_n(false),
+                AluOp::Adc => cpu.add_a_n(true),
+                AluOp::Sub => cpu.sub_a_n(false),
+                AluOp::Sbc => cpu.sub_a_n(true),
+                AluOp::And => cpu.and_a_n(),
+                AluOp::Xor => cpu.xor_a_n(),
+                AluOp::Or => cpu.or_a_n(),
+                AluOp::Cp => cpu.cp_a_n(),
+            }?;
+        }
+        Instruction::Rlca => {
+            cpu.rlca()?;
+        }
+        Instruction::Rrca => {
+            cpu.rrca()?;
+        }
+        Instruction::Rla => {
+            cpu.rla()?;
+        }
+        Instruction::Rra => {
+            cpu.rra()?;
+        }
+        Instruction::Daa => {
+            cpu.daa()?;
+        }
+        Instruction::Cpl => {
+            cpu.cpl()?;
+        }
+        Instruction::Scf => {
+            cpu.scf()?;
+        }
+        Instruction::Ccf => {
+            cpu.ccf()?;
+        }
 
-        // CALL 指令
-        0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => jump::dispatch(cpu, opcode), // CALL
+        // 跳躍、呼叫與堆疊
+        Instruction::Jp(condition) => taken = cpu.jp(condition)?,
+        Instruction::JpHl => {
+            cpu.jp_hl()?;
+        }
+        Instruction::Jr(condition) => taken = cpu.jr(condition)?,
+        Instruction::Call(condition) => taken = cpu.call(condition)?,
+        Instruction::Ret(condition) => taken = cpu.ret(condition)?,
+        Instruction::Reti => {
+            cpu.reti()?;
+        }
+        Instruction::Rst(vector) => {
+            cpu.rst(vector as u16)?;
+        }
+        Instruction::Push(rr) => {
+            match rr {
+                RegTarget::BC => cpu.push_bc(),
+                RegTarget::DE => cpu.push_de(),
+                RegTarget::HL => cpu.push_hl(),
+                _ => cpu.push_af(),
+            }?;
+        }
+        Instruction::Pop(rr) => {
+            match rr {
+                RegTarget::BC => cpu.pop_bc(),
+                RegTarget::DE => cpu.pop_de(),
+                RegTarget::HL => cpu.pop_hl(),
+                _ => cpu.pop_af(),
+            }?;
+        }
 
-        // RET/RETI 指令
-        0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9 => jump::dispatch(cpu, opcode), // RET/RETI
+        // CB 前綴：讀取第二個位元組後查 CB 表
+        Instruction::Prefix => {
+            let opcode = cpu.fetch_byte()?;
+            return execute(cpu, decode::decode_cb(opcode));
+        }
+        Instruction::Cb(op, target) => {
+            match op {
+                CbOp::Rlc => cpu.rlc_r(target),
+                CbOp::Rrc => cpu.rrc_r(target),
+                CbOp::Rl => cpu.rl_r(target),
+                CbOp::Rr => cpu.rr_r(target),
+                CbOp::Sla => cpu.sla_r(target),
+                CbOp::Sra => cpu.sra_r(target),
+                CbOp::Swap => cpu.swap_r(target),
+                CbOp::Srl => cpu.srl_r(target),
+                CbOp::Bit(bit) => cpu.bit_b_r(bit, target),
+                CbOp::Res(bit) => cpu.res_b_r(bit, target),
+                CbOp::Set(bit) => cpu.set_b_r(bit, target),
+            }?;
+        }
 
-        // 邏輯運算指令
-        0xA0..=0xA7 => logic::dispatch(cpu, opcode), // AND r
-        0xE6 => logic::dispatch(cpu, opcode),        // AND n
-        0xB0..=0xB7 => logic::dispatch(cpu, opcode), // OR r
-        0xF6 => logic::dispatch(cpu, opcode),        // OR n
-        0xA8..=0xAF => logic::dispatch(cpu, opcode), // XOR r
-        0xEE => logic::dispatch(cpu, opcode),        // XOR n
-        0xB8..=0xBF => logic::dispatch(cpu, opcode), // CP r
-        0xFE => logic::dispatch(cpu, opcode),        // CP n
-
-        // 未實現的指令
-        _ => Err(Error::Instruction(InstructionError::InvalidOpcode(opcode))),
+        // 未定義的操作碼使 CPU 當機，直到重設
+        Instruction::Illegal(opcode) => {
+            let pc = cpu.registers.get_pc().wrapping_sub(1);
+            log::warn!(
+                "Illegal opcode 0x{:02X} at PC=0x{:04X}, CPU locked",
+                opcode,
+                pc
+            );
+            cpu.locked = true;
+        }
     }
+
+    Ok(match instruction.branch_cycles() {
+        Some(cycles) if taken => cycles,
+        _ => instruction.cycles(),
+    })
 }
 
 // Re-export important components
@@ -28,6 +28,11 @@ impl Interrupt {
             _ => None,
         }
     }
+
+    /// Interrupt handler address (0x40, 0x48, 0x50, 0x58, 0x60)
+    pub fn vector(&self) -> u16 {
+        0x0040 + (self.to_bit() as u16) * 8
+    }
 }
 
 pub struct InterruptRegisters {
@@ -1,39 +1,50 @@
 use self::flags::Flag;
+use self::interrupts::InterruptRegisters;
+use crate::core::bus::Bus;
 use crate::core::cycles::*;
+use crate::core::mmu::boot::Model;
 use crate::core::mmu::MMU;
+use crate::core::state::{SaveState, StateReader, StateWriter};
 use crate::error::{Error, InstructionError, RegTarget, Result};
-use std::io::Write;
-use std::{cell::RefCell, rc::Rc};
 
+pub mod disassembler;
 pub mod flags;
 pub mod instructions;
 pub mod interrupts;
 pub mod registers;
+pub mod trace;
 
 use self::registers::Registers;
+use self::trace::Tracer;
 
 #[derive(Debug)]
-pub struct CPU {
+pub struct CPU<B: Bus = MMU> {
     registers: Registers,
-    mmu: Rc<RefCell<MMU>>,
+    bus: B,
     halted: bool,
+    halt_bug: bool, // Next opcode fetch doesn't advance PC
+    locked: bool,   // Hung by an illegal opcode until reset
     ime: bool,
     ime_scheduled: bool,
     instruction_count: u64,
+    cycles: CyclesType, // Elapsed in the current step
+    tracer: Option<Tracer>,
 }
 
-impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
-        // Create log directory
-        std::fs::create_dir_all("logs").ok();
-
+impl<B: Bus> CPU<B> {
+    pub fn new(bus: B) -> Self {
         // Create basic CPU instance
         let mut cpu = Self {
             registers: Registers::new(),
-            mmu,
+            bus,
             halted: false,
+            halt_bug: false,
+            locked: false,
             ime: false,
             ime_scheduled: false,
             instruction_count: 0,
+            cycles: 0,
+            tracer: None,
         };
 
         // Set standard register initial values according to Game Boy CPU Manual
@@ -42,7 +53,7 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
     }
 
     // Arithmetic instruction implementations
-    pub fn add_a_r(&mut self, source: RegTarget, use_carry: bool) -> Result<CyclesType> {
+    pub fn add_a_r(&mut self, source: RegTarget, use_carry: bool) -> Result<()> {
         let src_val = match source {
             RegTarget::A => self.registers.a,
             RegTarget::B => self.registers.b,
@@ -63,17 +74,13 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
         };
 
         self.add_a(src_val, use_carry);
-        Ok(if matches!(source, RegTarget::HL) {
-            CYCLES_2
-        } else {
-            CYCLES_1
-        })
+        Ok(())
     }
 
-    pub fn add_a_n(&mut self, use_carry: bool) -> Result<CyclesType> {
+    pub fn add_a_n(&mut self, use_carry: bool) -> Result<()> {
         let value = self.fetch_byte()?;
         self.add_a(value, use_carry);
-        Ok(CYCLES_2)
+        Ok(())
     }
 
     fn add_a(&mut self, value: u8, use_carry: bool) {
@@ -93,7 +100,7 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
         self.registers.a = result as u8;
     }
 
-    pub fn sub_a_r(&mut self, source: RegTarget, use_carry: bool) -> Result<CyclesType> {
+    pub fn sub_a_r(&mut self, source: RegTarget, use_carry: bool) -> Result<()> {
         let src_val = match source {
             RegTarget::A => self.registers.a,
             RegTarget::B => self.registers.b,
@@ -114,17 +121,13 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
         };
 
         self.sub_a(src_val, use_carry);
-        Ok(if matches!(source, RegTarget::HL) {
-            CYCLES_2
-        } else {
-            CYCLES_1
-        })
+        Ok(())
     }
 
-    pub fn sub_a_n(&mut self, use_carry: bool) -> Result<CyclesType> {
+    pub fn sub_a_n(&mut self, use_carry: bool) -> Result<()> {
         let value = self.fetch_byte()?;
         self.sub_a(value, use_carry);
-        Ok(CYCLES_2)
+        Ok(())
     }
 
     fn sub_a(&mut self, value: u8, use_carry: bool) {
@@ -145,49 +148,42 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
         self.registers.a = result;
     }
 
-    pub fn dec_r(&mut self, target: RegTarget) -> Result<CyclesType> {
-        let value = match target {
+    pub fn dec_r(&mut self, target: RegTarget) -> Result<()> {
+        match target {
             RegTarget::A => {
                 let result = self.registers.a.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.registers.a = result;
-                result
             }
             RegTarget::B => {
                 let result = self.registers.b.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.registers.b = result;
-                result
             }
             RegTarget::C => {
                 let result = self.registers.c.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.registers.c = result;
-                result
             }
             RegTarget::D => {
                 let result = self.registers.d.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.registers.d = result;
-                result
             }
             RegTarget::E => {
                 let result = self.registers.e.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.registers.e = result;
-                result
             }
             RegTarget::H => {
                 let result = self.registers.h.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.registers.h = result;
-                result
             }
             RegTarget::L => {
                 let result = self.registers.l.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.registers.l = result;
-                result
             }
             RegTarget::HL => {
                 let addr = self.registers.get_hl();
@@ -195,34 +191,15 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
                 let result = value.wrapping_sub(1);
                 self.set_dec_flags(result);
                 self.write_byte(addr, result)?;
-                result
             }
             _ => {
                 return Err(Error::Instruction(InstructionError::InvalidRegister(
                     target,
                 )))
             }
-        };
-
-        if let Ok(mut f) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("logs/cpu_exec.log")
-        {
-            writeln!(
-                f,
-                "DEC {:?}: {} -> {}",
-                target,
-                value.wrapping_add(1),
-                value
-            )?;
         }
 
-        Ok(if matches!(target, RegTarget::HL) {
-            CYCLES_3
-        } else {
-            CYCLES_1
-        })
+        Ok(())
     }
 
     fn set_dec_flags(&mut self, result: u8) {
@@ -231,10 +208,72 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
         self.registers.set_flag(Flag::H, (result & 0x0F) == 0x0F);
     }
 
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Interrupt master enable
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+
+    pub fn set_ime(&mut self, enabled: bool) {
+        self.ime = enabled;
+        self.ime_scheduled = false;
+    }
+
+    /// Whether an illegal opcode has hung the CPU
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Install an instruction tracer, returning the previous one
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.tracer, tracer)
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Advance the rest of the machine by one M-cycle. Called before every
+    /// bus access and for internal delays, so hardware sees each access in
+    /// the M-cycle it actually happens.
+    fn tick(&mut self) -> Result<()> {
+        self.cycles += CYCLES_1;
+        self.bus.tick(CYCLES_1)
+    }
+
+    /// Spend the internal delays left after an operation's last bus access
+    fn idle_until(&mut self, cycles: CyclesType) -> Result<CyclesType> {
+        while self.cycles < cycles {
+            self.tick()?;
+        }
+        Ok(self.cycles)
+    }
+
     // 其他輔助方法
     pub fn fetch_byte(&mut self) -> Result<u8> {
-        let byte = self.mmu.borrow().read_byte(self.registers.pc)?;
-        self.registers.pc = self.registers.pc.wrapping_add(1);
+        self.tick()?;
+        let byte = self.bus.read(self.registers.pc)?;
+        if self.halt_bug {
+            // HALT bug: PC fails to increment, so this byte is read twice
+            self.halt_bug = false;
+        } else {
+            self.registers.pc = self.registers.pc.wrapping_add(1);
+        }
         Ok(byte)
     }
 
@@ -245,11 +284,13 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
     }
 
     pub fn read_byte(&mut self, addr: u16) -> Result<u8> {
-        self.mmu.borrow().read_byte(addr)
+        self.tick()?;
+        self.bus.read(addr)
     }
 
     pub fn write_byte(&mut self, addr: u16, value: u8) -> Result<()> {
-        self.mmu.borrow_mut().write_byte(addr, value)
+        self.tick()?;
+        self.bus.write(addr, value)
     }
 
     pub fn reset(&mut self) -> Result<()> {
@@ -258,479 +299,383 @@ impl CPU {    pub fn new(mmu: Rc<RefCell<MMU>>) -> Self {
         self.registers.set_de(0x00D8);
         self.registers.set_hl(0x014D);
         self.registers.set_sp(0xFFFE);
-        self.registers.set_pc(0x0100);        self.halted = false;
+        self.registers.set_pc(0x0100);
+        self.halted = false;
+        self.halt_bug = false;
+        self.locked = false;
         self.ime = false;
         self.ime_scheduled = false;
         self.instruction_count = 0;
         Ok(())
     }
 
+    /// Registers as the model's boot ROM leaves them when it jumps to 0x0100
+    pub fn apply_post_boot_state(&mut self, model: Model, header_checksum: u8) -> Result<()> {
+        self.reset()?;
+        let (af, bc, de, hl) = match model {
+            // H and C stay set unless the header checksum is zero
+            Model::Dmg | Model::Mgb => {
+                let a: u16 = if model == Model::Mgb { 0xFF } else { 0x01 };
+                let f: u16 = if header_checksum == 0 { 0x80 } else { 0xB0 };
+                (a << 8 | f, 0x0013, 0x00D8, 0x014D)
+            }
+            Model::Cgb => (0x1180, 0x0000, 0xFF56, 0x000D),
+        };
+        self.registers.set_af(af);
+        self.registers.set_bc(bc);
+        self.registers.set_de(de);
+        self.registers.set_hl(hl);
+        Ok(())
+    }
+
+    /// Power-on state: everything cleared and execution starting in the boot ROM
+    pub fn start_boot_rom(&mut self) -> Result<()> {
+        self.reset()?;
+        self.registers.set_af(0);
+        self.registers.set_bc(0);
+        self.registers.set_de(0);
+        self.registers.set_hl(0);
+        self.registers.set_sp(0);
+        self.registers.set_pc(0x0000);
+        Ok(())
+    }
+
     // Stack 操作
+    /// Every push (PUSH, CALL, RST, interrupts) starts with an internal
+    /// cycle, then writes the high byte first
     pub fn push_word(&mut self, value: u16) -> Result<()> {
-        self.registers.sp = self.registers.sp.wrapping_sub(2);
-        let sp = self.registers.sp;
+        self.tick()?;
+        let sp = self.registers.sp.wrapping_sub(1);
+        self.write_byte(sp, (value >> 8) as u8)?;
+        let sp = sp.wrapping_sub(1);
         self.write_byte(sp, (value & 0xFF) as u8)?;
-        self.write_byte(sp + 1, (value >> 8) as u8)?;
+        self.registers.sp = sp;
         Ok(())
     }
 
     pub fn pop_word(&mut self) -> Result<u16> {
         let sp = self.registers.sp;
         let low = self.read_byte(sp)? as u16;
-        let high = self.read_byte(sp + 1)? as u16;
+        let high = self.read_byte(sp.wrapping_add(1))? as u16;
         self.registers.sp = self.registers.sp.wrapping_add(2);
         Ok((high << 8) | low)
     }
 
-    fn decode_opcode_name(opcode: u8) -> &'static str {
-        match opcode {
-            // NOP
-            0x00 => "NOP",            // LD instruction family
-            0x21 => "LD HL,nn",   // Load immediate 16-bit value to HL
-            0x01 => "LD BC,nn",   // Load immediate 16-bit value to BC
-            0x11 => "LD DE,nn",   // Load immediate 16-bit value to DE
-            0x31 => "LD SP,nn",   // Load immediate 16-bit value to SP
-            0x06 => "LD B,n",     // Load immediate 8-bit value to B
-            0x0E => "LD C,n",     // Load immediate 8-bit value to C
-            0x32 => "LD (HL-),A", // Load A to (HL) and decrement HL
-
-            // Decrement instructions
-            0x05 => "DEC B", // Decrement B
-
-            // Jump instructions
-            0xC3 => "JP nn", // Jump to immediate address
-
-            // Conditional jump instructions
-            0xC2 => "JP NZ,nn", // Jump if not zero
-            0xCA => "JP Z,nn",  // Jump if zero
-            0xD2 => "JP NC,nn", // Jump if no carry
-            0xDA => "JP C,nn",  // Jump if carry
-            0xE9 => "JP HL",    // Jump to HL
-
-            // CALL instructions
-            0xCD => "CALL nn",    // Unconditional call
-            0xC4 => "CALL NZ,nn", // Call if not zero
-            0xCC => "CALL Z,nn",  // Call if zero
-            0xD4 => "CALL NC,nn", // Call if no carry
-            0xDC => "CALL C,nn",  // Call if carry
-
-            // RET instructions
-            0xC9 => "RET",    // Unconditional return
-            0xC0 => "RET NZ", // Return if not zero
-            0xC8 => "RET Z",  // Return if zero
-            0xD0 => "RET NC", // Return if no carry
-            0xD8 => "RET C",  // Return if carry
-            0xD9 => "RETI",   // Return from interrupt
-
-            // Logic instructions
-            0xA8..=0xAE => "XOR r", // XOR with register
-            0xAF => "XOR A",        // XOR A with itself (sets A to 0)
-            0xEE => "XOR n",        // XOR with immediate value
-            0xB0..=0xB7 => "OR r",  // OR with register
-            0xF6 => "OR n",         // OR with immediate value
-            0xA0..=0xA7 => "AND r", // AND with register
-            0xE6 => "AND n",        // AND with immediate value
-            0xB8..=0xBF => "CP r",  // Compare with register
-            0xFE => "CP n",         // Compare with immediate value
-
-            // Stack operations
-            0xC5 => "PUSH BC", // Push BC onto stack
-            0xD5 => "PUSH DE", // Push DE onto stack
-            0xE5 => "PUSH HL", // Push HL onto stack
-            0xF5 => "PUSH AF", // Push AF onto stack
-            0xC1 => "POP BC",  // Pop BC from stack
-            0xD1 => "POP DE",  // Pop DE from stack
-            0xE1 => "POP HL",  // Pop HL from stack
-            0xF1 => "POP AF",  // Pop AF from stack
-
-            // JR instructions
-            0x20 => "JR NZ,n", // Relative jump if not zero
-            0x28 => "JR Z,n",  // Relative jump if zero
-            0x30 => "JR NC,n", // Relative jump if no carry
-            0x38 => "JR C,n",  // Relative jump if carry
-            0x18 => "JR n",    // Unconditional relative jump
-
-            _ => "UNKNOWN",
+    /// Run one instruction, interrupt dispatch or idle HALT cycle. The MMU and
+    /// attached hardware are clocked as it goes; returns the T-cycles taken.
+    pub fn step(&mut self) -> Result<CyclesType> {
+        self.cycles = 0;
+
+        // Illegal opcodes hang the CPU for good; not even interrupts get it going again
+        if self.locked {
+            return self.idle_until(CYCLES_1);
         }
-    }    pub fn step(&mut self) -> Result<CyclesType> {
-        let pc = self.registers.get_pc();
-        let opcode = self.fetch_byte()?;        // Basic CPU state logging for debugging
-        self.instruction_count += 1;
-        if self.instruction_count % 10000 == 0 {
-            if let Ok(mut file) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("logs/cpu_status.log")
-            {
-                writeln!(file, "CPU Status: PC=0x{:04X}, Instruction Count: {}", pc, self.instruction_count).ok();
-            }
+
+        // Interrupts are serviced between instructions; any pending one also ends HALT
+        if let Some(cycles) = self.handle_interrupts()? {
+            return self.idle_until(cycles);
+        }
+        if self.halted {
+            return self.idle_until(CYCLES_1);
         }
 
-        let cycles = match opcode {
-            // NOP
-            0x00 => Ok(CYCLES_1),            // DEC r
-            0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D => {
-                // Disabled logging for performance during long loops
-                /*
-                writeln!(
-                    std::fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open("logs/cpu_exec.log")
-                        .unwrap(),
-                    "Executing DEC instruction: 0x{:02X}",
-                    opcode
-                ).ok();
-                */
-                self::instructions::arithmetic::dispatch(self, opcode)
-            },            // LD instruction family
-            0x01 | 0x11 | 0x21 | 0x31 | // LD rr,nn
-            0x40..=0x7F |               // LD r,r'
-            0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x3E | // LD r,n
-            0x02 | 0x12 | 0x22 | 0x32 | // LD (rr),A
-            0x0A | 0x1A | 0x2A | 0x3A | // LD A,(rr)
-            0x36 |                      // LD (HL),n
-            0x08 | 0xE0 | 0xE2 | 0xEA | 0xF0 | 0xF2 | 0xF8 | 0xF9 | 0xFA => {
-                // Disabled logging for performance during long loops
-                /*
-                writeln!(
-                    std::fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open("logs/cpu_exec.log")
-                        .unwrap(),
-                    "Executing LD instruction: 0x{:02X}",
-                    opcode
-                ).ok();
-                */
-                self::instructions::load::dispatch(self, opcode)
-            },
-
-            // 邏輯運算指令（AND、OR、XOR、CP）
-            0xA0..=0xA7 | 0xA8..=0xAF | 0xB0..=0xB7 | 0xB8..=0xBF | 0xE6 | 0xEE | 0xF6 | 0xFE => {
-                self::instructions::logic::dispatch(self, opcode)
-            },
-
-            // JP nn（無條件跳轉）
-            0xC3 => {
-                let address = self.fetch_word()?;
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(file, "無條件跳轉到 0x{:04X}", address).ok();
-                }
-                self.registers.pc = address;
-                Ok(CYCLES_4)
-            }
+        // EI takes effect only after the instruction following it
+        let enable_ime = self.ime_scheduled;
 
-            // JP cc,nn（條件跳轉）
-            0xC2 | 0xCA | 0xD2 | 0xDA => {
-                let address = self.fetch_word()?;
-                let condition = match opcode {
-                    0xC2 => !self.registers.get_flag(Flag::Z),  // JP NZ,nn
-                    0xCA => self.registers.get_flag(Flag::Z),   // JP Z,nn
-                    0xD2 => !self.registers.get_flag(Flag::C),  // JP NC,nn
-                    0xDA => self.registers.get_flag(Flag::C),   // JP C,nn
-                    _ => unreachable!()
-                };
-
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(
-                        file,
-                        "條件跳轉檢查: {}={}，目標地址=0x{:04X}",
-                        Self::decode_opcode_name(opcode),
-                        condition,
-                        address
-                    ).ok();
-                }
-
-                if condition {
-                    self.registers.pc = address;
-                    Ok(CYCLES_4)
-                } else {
-                    Ok(CYCLES_3)
-                }
+        if let Some(tracer) = self.tracer.as_mut() {
+            let pc = self.registers.get_pc();
+            let mut pcmem = [0; 4];
+            for (i, byte) in pcmem.iter_mut().enumerate() {
+                *byte = self.bus.peek(pc.wrapping_add(i as u16))?;
             }
+            tracer.trace(&self.registers, pcmem)?;
+        }
 
-            // JP HL
-            0xE9 => {
-                let address = self.registers.get_hl();
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(file, "跳轉到 HL=0x{:04X}", address).ok();
-                }
-                self.registers.pc = address;
-                Ok(CYCLES_1)
-            }
+        let opcode = self.fetch_byte()?;
+        self.instruction_count += 1;
 
-            // CALL nn（無條件調用）
-            0xCD => {
-                let address = self.fetch_word()?;
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(
-                        file,
-                        "調用子程序: PC=0x{:04X} -> 0x{:04X}",
-                        self.registers.pc,
-                        address
-                    ).ok();
-                }
-                self.push_word(self.registers.pc)?;
-                self.registers.pc = address;
-                Ok(CYCLES_6)
-            }
+        let cycles = instructions::execute(self, instructions::decode::decode(opcode))?;
+        let cycles = self.idle_until(cycles)?;
 
-            // CALL cc,nn（條件調用）
-            0xC4 | 0xCC | 0xD4 | 0xDC => {
-                let address = self.fetch_word()?;
-                let condition = match opcode {
-                    0xC4 => !self.registers.get_flag(Flag::Z),  // CALL NZ,nn
-                    0xCC => self.registers.get_flag(Flag::Z),   // CALL Z,nn
-                    0xD4 => !self.registers.get_flag(Flag::C),  // CALL NC,nn
-                    0xDC => self.registers.get_flag(Flag::C),   // CALL C,nn
-                    _ => unreachable!()
-                };
-
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(
-                        file,
-                        "條件調用檢查: {}={}，目標地址=0x{:04X}",
-                        Self::decode_opcode_name(opcode),
-                        condition,
-                        address
-                    ).ok();
-                }
-
-                if condition {
-                    self.push_word(self.registers.pc)?;
-                    self.registers.pc = address;
-                    Ok(CYCLES_6)
-                } else {
-                    Ok(CYCLES_3)
-                }
-            }
+        if enable_ime && self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
+        }
 
-            // RET（無條件返回）
-            0xC9 => {
-                let address = self.pop_word()?;
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(file, "返回到 0x{:04X}", address).ok();
-                }
-                self.registers.pc = address;
-                Ok(CYCLES_4)
-            }
+        Ok(cycles)
+    }
 
-            // RET cc（條件返回）
-            0xC0 | 0xC8 | 0xD0 | 0xD8 => {
-                let condition = match opcode {
-                    0xC0 => !self.registers.get_flag(Flag::Z),  // RET NZ
-                    0xC8 => self.registers.get_flag(Flag::Z),   // RET Z
-                    0xD0 => !self.registers.get_flag(Flag::C),  // RET NC
-                    0xD8 => self.registers.get_flag(Flag::C),   // RET C
-                    _ => unreachable!()
-                };
-
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(
-                        file,
-                        "條件返回檢查: {}={}",
-                        Self::decode_opcode_name(opcode),
-                        condition
-                    ).ok();
-                }
-
-                if condition {
-                    let address = self.pop_word()?;
-                    self.registers.pc = address;
-                    Ok(CYCLES_5)
-                } else {
-                    Ok(CYCLES_2)
-                }
-            }            // RETI (Return from interrupt)
-            0xD9 => {
-                let address = self.pop_word()?;
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(file, "Return from interrupt to 0x{:04X}", address).ok();
-                }
-                self.registers.pc = address;
-                self.ime = true; // Enable interrupts
-                Ok(CYCLES_4)
-            }            // JR 指令族
-            0x20 | 0x28 | 0x30 | 0x38 => {
-                let offset = self.fetch_byte()? as i8;
-                let condition = match opcode {
-                    0x20 => !self.registers.get_flag(Flag::Z),     // JR NZ,n
-                    0x28 => self.registers.get_flag(Flag::Z),      // JR Z,n
-                    0x30 => !self.registers.get_flag(Flag::C),     // JR NC,n
-                    0x38 => self.registers.get_flag(Flag::C),      // JR C,n
-                    _ => unreachable!()
-                };
-                
-                // Only log when condition changes or every 100 jumps to reduce log spam
-                if !condition || self.instruction_count % 100 == 0 {
-                    if let Ok(mut file) = std::fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open("logs/cpu_exec.log")
-                    {
-                        let target = ((self.registers.pc as i32) + (offset as i32)) as u16;
-                        writeln!(
-                            file,
-                            "JR check: {}={}, offset={:+}, target=0x{:04X}, inst={}",
-                            Self::decode_opcode_name(opcode),
-                            condition,
-                            offset,
-                            target,
-                            self.instruction_count
-                        ).ok();
-                    }
-                }
-
-                if condition {
-                    self.registers.pc = ((self.registers.pc as i32) + (offset as i32)) as u16;
-                    Ok(CYCLES_3)
-                } else {
-                    Ok(CYCLES_2)
-                }
-            }// JR n（無條件相對跳轉）
-            0x18 => {
-                let offset = self.fetch_byte()? as i8;
-                let target = ((self.registers.pc as i32) + (offset as i32)) as u16;
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(
-                        file,
-                        "無條件相對跳轉：偏移量={:+}，從 0x{:04X} 跳轉到 0x{:04X}",
-                        offset,
-                        self.registers.pc,
-                        target
-                    ).ok();
-                }
-                self.registers.pc = target;
-                Ok(CYCLES_3)
-            }            // DI (Disable Interrupts)
-            0xF3 => {
-                // Disabled logging for performance
-                /*
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(file, "Disable interrupts (DI)").ok();
-                }
-                */
-                self.ime = false;
-                Ok(CYCLES_1)
-            }            // EI (Enable Interrupts)  
-            0xFB => {
-                // Disabled logging for performance
-                /*
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(file, "Enable interrupts (EI)").ok();
-                }
-                */
-                self.ime_scheduled = true; // Enable after next instruction
-                Ok(CYCLES_1)
-            }
+    /// Whether any enabled interrupt is requested, regardless of IME
+    pub fn interrupt_pending(&self) -> bool {
+        self.bus.interrupt_enable() & self.bus.interrupt_flags() & 0x1F != 0
+    }
 
-            // 其他指令將在後續添加
-            _ => {
-                if let Ok(mut file) = std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("logs/cpu_exec.log")
-                {
-                    writeln!(file, "未知指令: 0x{:02X} at PC=0x{:04X}", opcode, pc).ok();
-                }
-                Err(Error::Instruction(InstructionError::InvalidOpcode(opcode)))
-            }        }?;
-
-        // Handle interrupts after instruction execution
-        if self.ime && !self.halted {
-            if let Ok(interrupt_cycles) = self.handle_interrupts() {
-                return Ok(cycles + interrupt_cycles);
-            }
+    /// Service the highest-priority pending interrupt.
+    /// Returns the cycles spent, or `None` if execution should continue normally.
+    fn handle_interrupts(&mut self) -> Result<Option<CyclesType>> {
+        let registers = InterruptRegisters {
+            enable: self.bus.interrupt_enable(),
+            flag: self.bus.interrupt_flags(),
+        };
+
+        let interrupt = match registers.get_highest_priority_interrupt() {
+            Some(interrupt) => interrupt,
+            None => return Ok(None),
+        };
+
+        // A pending interrupt ends HALT even when IME is off; execution then just resumes
+        let woke_from_halt = self.halted;
+        self.halted = false;
+        if !self.ime {
+            return Ok(None);
         }
 
-        // Handle scheduled interrupt enable
-        if self.ime_scheduled {
-            self.ime = true;
-            self.ime_scheduled = false;
+        self.ime = false;
+        let flags = self.bus.interrupt_flags();
+        self.bus
+            .set_interrupt_flags(flags & !(1 << interrupt.to_bit()));
+        self.tick()?;
+        self.push_word(self.registers.get_pc())?;
+        self.registers.set_pc(interrupt.vector());
+
+        log::trace!(
+            "{:?} interrupt handled, jumping to 0x{:04X}",
+            interrupt,
+            interrupt.vector()
+        );
+
+        // Dispatch takes 5 M-cycles, plus one more to leave HALT
+        Ok(Some(if woke_from_halt { CYCLES_6 } else { CYCLES_5 }))
+    }
+}
+
+// Registers and execution state, followed by everything behind the bus
+impl<B: Bus + SaveState> SaveState for CPU<B> {
+    fn save_state(&self, w: &mut StateWriter) {
+        let r = &self.registers;
+        for pair in [r.get_af(), r.get_bc(), r.get_de(), r.get_hl(), r.sp, r.pc] {
+            w.u16(pair);
         }
+        w.bool(self.halted);
+        w.bool(self.halt_bug);
+        w.bool(self.locked);
+        w.bool(self.ime);
+        w.bool(self.ime_scheduled);
+        w.u64(self.instruction_count);
+        self.bus.save_state(w);
+    }
 
-        Ok(cycles)
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.registers.set_af(r.u16()?);
+        self.registers.set_bc(r.u16()?);
+        self.registers.set_de(r.u16()?);
+        self.registers.set_hl(r.u16()?);
+        self.registers.sp = r.u16()?;
+        self.registers.pc = r.u16()?;
+        self.halted = r.bool()?;
+        self.halt_bug = r.bool()?;
+        self.locked = r.bool()?;
+        self.ime = r.bool()?;
+        self.ime_scheduled = r.bool()?;
+        self.instruction_count = r.u64()?;
+        self.bus.load_state(r)
     }
+}
 
-    // Handle interrupts
-    fn handle_interrupts(&mut self) -> Result<CyclesType> {
-        let ie = self.read_byte(0xFFFF)?; // Interrupt Enable
-        let if_reg = self.read_byte(0xFF0F)?; // Interrupt Flag
-        
-        let pending = ie & if_reg;
-        if pending == 0 {
-            return Ok(CYCLES_1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// MMU with `program` in work RAM at 0xC000
+    fn mmu_with_program(program: &[u8]) -> MMU {
+        let mut mmu = MMU::new();
+        for (i, &byte) in program.iter().enumerate() {
+            mmu.write_byte(0xC000 + i as u16, byte).unwrap();
         }
+        mmu
+    }
 
-        // Check for V-Blank interrupt (bit 0)
-        if pending & 0x01 != 0 {
-            // Clear the interrupt flag
-            self.write_byte(0xFF0F, if_reg & !0x01)?;
-            
-            // Disable interrupts
-            self.ime = false;
-            
-            // Push PC onto stack and jump to interrupt vector
-            self.push_word(self.registers.get_pc())?;
-            self.registers.set_pc(0x0040); // V-Blank interrupt vector
-            
-            if let Ok(mut file) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("logs/interrupt.log")
-            {
-                writeln!(file, "V-Blank interrupt handled at PC=0x{:04X}", self.registers.get_pc()).ok();
-            }
-            
-            return Ok(CYCLES_4);
+    /// CPU running `program` from work RAM at 0xC000
+    fn cpu_with_program(program: &[u8]) -> CPU {
+        let mut cpu = CPU::new(mmu_with_program(program));
+        cpu.registers.set_pc(0xC000);
+        cpu
+    }
+
+    #[test]
+    fn test_interrupts_dispatch_by_priority() {
+        let mut cpu = cpu_with_program(&[0x00]);
+        cpu.ime = true;
+        cpu.bus.interrupt_enable = 0x1F;
+        cpu.bus.interrupt_flags = 0x0C; // Timer and Serial
+
+        assert_eq!(cpu.step().unwrap(), CYCLES_5);
+        assert_eq!(cpu.registers.get_pc(), 0x0050);
+        assert_eq!(cpu.bus.interrupt_flags, 0x08);
+        assert!(!cpu.ime);
+    }
+
+    #[test]
+    fn test_halt_wakes_without_ime() {
+        let mut cpu = cpu_with_program(&[0x76, 0x00]);
+        cpu.bus.interrupt_enable = 0x04;
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.get_pc(), 0xC001);
+
+        cpu.bus.interrupt_flags = 0x04;
+        cpu.step().unwrap();
+        assert!(!cpu.is_halted());
+        assert_eq!(cpu.registers.get_pc(), 0xC002);
+        assert_eq!(cpu.bus.interrupt_flags, 0x04);
+    }
+
+    #[test]
+    fn test_halt_bug_repeats_next_byte() {
+        // HALT; LD B,n with the opcode byte read again as its operand
+        let mut cpu = cpu_with_program(&[0x76, 0x06, 0x42]);
+        cpu.bus.interrupt_enable = 0x01;
+        cpu.bus.interrupt_flags = 0x01;
+
+        cpu.step().unwrap();
+        assert!(!cpu.is_halted());
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.b, 0x06);
+        assert_eq!(cpu.registers.get_pc(), 0xC002);
+    }
+
+    #[test]
+    fn test_alu_and_cb_opcodes_through_step() {
+        let mut cpu = cpu_with_program(&[
+            0x3E, 0x45, // LD A,0x45
+            0xC6, 0x38, // ADD A,0x38
+            0x27, // DAA
+            0x21, 0x00, 0xD0, // LD HL,0xD000
+            0xCB, 0xC6, // SET 0,(HL)
+            0x34, // INC (HL)
+            0xCB, 0x46, // BIT 0,(HL)
+        ]);
+        let cycles: Vec<_> = (0..7).map(|_| cpu.step().unwrap()).collect();
+        assert_eq!(cycles, [8, 8, 4, 12, 16, 12, 12]);
+
+        // 45 + 38 = 83 in BCD
+        assert_eq!(cpu.registers.a, 0x83);
+        assert_eq!(cpu.bus.read_byte(0xD000).unwrap(), 0x02);
+        assert!(cpu.registers.get_flag(Flag::Z));
+        assert!(cpu.registers.get_flag(Flag::H));
+    }
+
+    /// MMU that counts the M-cycles it is clocked for
+    #[derive(Debug)]
+    struct CountingBus {
+        mmu: MMU,
+        ticks: u32,
+    }
+
+    impl Tick for CountingBus {
+        fn tick(&mut self, cycles: CyclesType) -> Result<()> {
+            self.ticks += cycles / CYCLES_1;
+            self.mmu.tick(cycles)
+        }
+    }
+
+    impl Bus for CountingBus {
+        fn read(&mut self, address: u16) -> Result<u8> {
+            self.mmu.read(address)
+        }
+
+        fn write(&mut self, address: u16, value: u8) -> Result<()> {
+            self.mmu.write(address, value)
+        }
+
+        fn peek(&self, address: u16) -> Result<u8> {
+            self.mmu.peek(address)
+        }
+
+        fn interrupt_enable(&self) -> u8 {
+            self.mmu.interrupt_enable
+        }
+
+        fn interrupt_flags(&self) -> u8 {
+            self.mmu.interrupt_flags
+        }
+
+        fn set_interrupt_flags(&mut self, value: u8) {
+            self.mmu.interrupt_flags = value;
+        }
+    }
+
+    #[test]
+    fn test_bus_accesses_tick_hardware_first() {
+        // LDH A,(05); PUSH BC; RET NZ
+        let mut mmu = mmu_with_program(&[0xF0, 0x05, 0xC5, 0xC0]);
+        mmu.write_byte(0xFF07, 0x05).unwrap(); // TIMA every 4 M-cycles
+        mmu.timer.set_counter(4);
+        let mut cpu = CPU::new(CountingBus { mmu, ticks: 0 });
+        cpu.registers.set_pc(0xC000);
+
+        // The read in the third M-cycle already sees TIMA incremented
+        assert_eq!(cpu.step().unwrap(), CYCLES_3);
+        assert_eq!(cpu.registers.a, 1);
+        assert_eq!(cpu.bus.ticks, 3);
+
+        assert_eq!(cpu.step().unwrap(), CYCLES_4);
+        cpu.registers.set_flag(Flag::Z, true);
+        assert_eq!(cpu.step().unwrap(), CYCLES_2);
+        assert_eq!(cpu.bus.ticks, 9);
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
         }
-        
-        // Handle other interrupts similarly...
-        Ok(CYCLES_1)
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_tracer_logs_between_start_and_stop() {
+        use self::trace::TraceCondition;
+
+        let mut cpu = cpu_with_program(&[0x00, 0x00, 0x00, 0x3C]);
+        let sink = SharedSink::default();
+        let tracer = Tracer::new(Box::new(sink.clone()))
+            .start_when(TraceCondition::AfterInstructions(1))
+            .stop_when(TraceCondition::PcEquals(0xC003));
+        cpu.set_tracer(Some(tracer));
+        for _ in 0..4 {
+            cpu.step().unwrap();
+        }
+
+        let log = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C001 PCMEM:00,00,3C,00"
+        );
+        assert!(cpu.set_tracer(None).unwrap().is_stopped());
+    }
+
+    #[test]
+    fn test_illegal_opcode_locks_cpu() {
+        let mut cpu = cpu_with_program(&[0xD3, 0x00]);
+        cpu.step().unwrap();
+        assert!(cpu.is_locked());
+
+        // Interrupts can't wake it up either
+        cpu.ime = true;
+        cpu.bus.interrupt_enable = 0x01;
+        cpu.bus.interrupt_flags = 0x01;
+        assert_eq!(cpu.step().unwrap(), CYCLES_1);
+        assert_eq!(cpu.registers.get_pc(), 0xC001);
     }
 }
@@ -0,0 +1,130 @@
+// Instruction tracer in the Gameboy Doctor log format
+
+use super::registers::Registers;
+use crate::error::Result;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Point at which a tracer starts or stops writing lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceCondition {
+    /// Once this many instructions have executed since the tracer was attached
+    AfterInstructions(u64),
+    /// When the next instruction is at this address
+    PcEquals(u16),
+}
+
+/// Writes one line per executed instruction, e.g.
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+pub struct Tracer {
+    sink: Box<dyn Write + Send>,
+    start: Option<TraceCondition>,
+    stop: Option<TraceCondition>,
+    started: bool,
+    stopped: bool,
+    instructions: u64,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("start", &self.start)
+            .field("stop", &self.stop)
+            .field("started", &self.started)
+            .field("stopped", &self.stopped)
+            .field("instructions", &self.instructions)
+            .finish()
+    }
+}
+
+impl Tracer {
+    /// Trace every instruction into `sink`
+    pub fn new(sink: Box<dyn Write + Send>) -> Self {
+        Self {
+            sink,
+            start: None,
+            stop: None,
+            started: true,
+            stopped: false,
+            instructions: 0,
+        }
+    }
+
+    /// Trace into a buffered file at `path`, replacing any existing one
+    pub fn to_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+
+    /// Hold off tracing until `condition` is met
+    pub fn start_when(mut self, condition: TraceCondition) -> Self {
+        self.start = Some(condition);
+        self.started = false;
+        self
+    }
+
+    /// Stop tracing for good once `condition` is met
+    pub fn stop_when(mut self, condition: TraceCondition) -> Self {
+        self.stop = Some(condition);
+        self
+    }
+
+    /// Whether the stop condition has been reached
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Record the state before the instruction at PC executes.
+    /// `pcmem` holds the four bytes at PC..PC+3.
+    pub fn trace(&mut self, registers: &Registers, pcmem: [u8; 4]) -> Result<()> {
+        let pc = registers.get_pc();
+        let count = self.instructions;
+        self.instructions += 1;
+        if self.stopped {
+            return Ok(());
+        }
+
+        let met = |condition: TraceCondition| match condition {
+            TraceCondition::AfterInstructions(n) => count >= n,
+            TraceCondition::PcEquals(address) => pc == address,
+        };
+        if self.stop.is_some_and(met) {
+            self.stopped = true;
+            self.sink.flush()?;
+            return Ok(());
+        }
+        if !self.started {
+            self.started = self.start.is_some_and(met);
+            if !self.started {
+                return Ok(());
+            }
+        }
+
+        writeln!(
+            self.sink,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            registers.a,
+            registers.get_f(),
+            registers.b,
+            registers.c,
+            registers.d,
+            registers.e,
+            registers.h,
+            registers.l,
+            registers.get_sp(),
+            pc,
+            pcmem[0],
+            pcmem[1],
+            pcmem[2],
+            pcmem[3]
+        )?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.sink.flush()?;
+        Ok(())
+    }
+}
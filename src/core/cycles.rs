@@ -1,3 +1,5 @@
+use crate::error::Result;
+
 /// CPU 和其他硬體組件共用的時脈週期型別
 pub type CyclesType = u32;
 
@@ -13,3 +15,9 @@ pub const CYCLES_6: CyclesType = 24;   // 6 M-cycles = 24 T-cycles
 pub const CPU_CLOCK: CyclesType = 4_194_304;  // 4.194304 MHz
 pub const PPU_LINE_CYCLES: CyclesType = 456;   // 掃描線週期數
 pub const PPU_FRAME_CYCLES: CyclesType = 70224; // 幀週期數
+
+/// 與 CPU 同步推進的硬體，匯流排在每次 CPU 存取前推進一個 M-cycle
+pub trait Tick: std::fmt::Debug {
+    /// 推進 `cycles` 個時脈週期，CPU 每次呼叫固定為一個 M-cycle
+    fn tick(&mut self, cycles: CyclesType) -> Result<()>;
+}
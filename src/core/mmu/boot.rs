@@ -0,0 +1,56 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Size of the DMG and MGB boot ROMs
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+/// Size of the CGB boot ROM, including the unused 0x0100-0x01FF hole
+pub const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+/// Hardware model, selects the boot ROM layout and the post-boot register state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Model {
+    #[default]
+    Dmg,
+    Mgb,
+    Cgb,
+}
+
+/// Boot ROM image overlaid on the start of the cartridge until 0xFF50 is written
+#[derive(Debug, Clone)]
+pub struct BootRom {
+    data: Vec<u8>,
+    model: Model,
+}
+
+impl BootRom {
+    /// Wrap a boot ROM dump for `model`. DMG and MGB boot ROMs are 256 bytes,
+    /// CGB ones 2304; an image of the wrong size is rejected.
+    pub fn new(data: Vec<u8>, model: Model) -> Result<Self> {
+        let expected = match model {
+            Model::Dmg | Model::Mgb => DMG_BOOT_ROM_SIZE,
+            Model::Cgb => CGB_BOOT_ROM_SIZE,
+        };
+        if data.len() != expected {
+            return Err(Error::Config(format!(
+                "Boot ROM is {} bytes, a {:?} boot ROM is {} bytes",
+                data.len(),
+                model,
+                expected
+            )));
+        }
+        Ok(Self { data, model })
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Boot ROM byte at `address`, or `None` where the cartridge shows through
+    pub fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000..=0x00FF => Some(self.data[address as usize]),
+            0x0200..=0x08FF if self.model == Model::Cgb => Some(self.data[address as usize]),
+            _ => None,
+        }
+    }
+}
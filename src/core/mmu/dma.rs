@@ -0,0 +1,138 @@
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::{Result, StateError};
+
+/// Number of bytes copied into OAM by one transfer
+pub const OAM_DMA_LENGTH: u16 = 0xA0;
+
+/// OAM DMA engine started by writing the source page to 0xFF46
+///
+/// After a 1 M-cycle start-up delay it copies one byte per M-cycle from
+/// `page << 8` to 0xFE00, keeping the bus busy for 160 M-cycles.
+#[derive(Debug, Clone, Default)]
+pub struct Dma {
+    source: u16,
+    index: u16,
+    active: bool,
+    pending: Option<u16>, // Source of a transfer waiting out its start-up cycle
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a transfer currently owns the bus
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Request a transfer from `page << 8`
+    ///
+    /// Restarting while a transfer runs doesn't free the bus: the old transfer
+    /// keeps copying until the new one takes over after its start-up cycle.
+    pub fn start(&mut self, page: u8) {
+        let mut source = (page as u16) << 8;
+        // 0xE000-0xFFFF sources read the work RAM mirror, like Echo RAM
+        if source >= 0xE000 {
+            source -= 0x2000;
+        }
+        self.pending = Some(source);
+    }
+
+    /// Advance one M-cycle. Returns the (source address, OAM offset) of the
+    /// byte to copy in this cycle, if any.
+    pub fn tick(&mut self) -> Option<(u16, usize)> {
+        let mut transfer = None;
+        if self.active {
+            transfer = Some((self.source + self.index, self.index as usize));
+            self.index += 1;
+            if self.index == OAM_DMA_LENGTH {
+                self.active = false;
+            }
+        }
+
+        if let Some(source) = self.pending.take() {
+            self.source = source;
+            self.index = 0;
+            self.active = true;
+        }
+
+        transfer
+    }
+}
+
+impl SaveState for Dma {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.source);
+        w.u16(self.index);
+        w.bool(self.active);
+        w.bool(self.pending.is_some());
+        w.u16(self.pending.unwrap_or(0));
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        let source = r.u16()?;
+        let index = r.u16()?;
+        let active = r.bool()?;
+        let pending = r.bool()?;
+        let pending_source = r.u16()?;
+
+        // A finished transfer leaves `index` at the end, a running one must
+        // still have bytes left and a source range that doesn't wrap
+        let fits = |source: u16| source.checked_add(OAM_DMA_LENGTH - 1).is_some();
+        let in_range = if active {
+            index < OAM_DMA_LENGTH && fits(source)
+        } else {
+            index <= OAM_DMA_LENGTH
+        };
+        if !in_range || (pending && !fits(pending_source)) {
+            return Err(StateError::Corrupt.into());
+        }
+
+        self.source = source;
+        self.index = index;
+        self.active = active;
+        self.pending = pending.then_some(pending_source);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn load(source: u16, index: u16, active: bool, pending: Option<u16>) -> Result<Dma> {
+        let mut w = StateWriter::new();
+        w.u16(source);
+        w.u16(index);
+        w.bool(active);
+        w.bool(pending.is_some());
+        w.u16(pending.unwrap_or(0));
+        let data = w.into_inner();
+        let mut dma = Dma::new();
+        dma.load_state(&mut StateReader::new(&data))?;
+        Ok(dma)
+    }
+
+    #[test]
+    fn test_load_state_rejects_out_of_range_transfer() {
+        // A finished transfer and one in flight are both valid
+        assert!(load(0xC000, OAM_DMA_LENGTH, false, None).is_ok());
+        let mut dma = load(0xC000, 0x9F, true, None).unwrap();
+        assert_eq!(dma.tick(), Some((0xC09F, 0x9F)));
+        assert!(!dma.is_active());
+
+        for (source, index, active, pending) in [
+            (0xC000, OAM_DMA_LENGTH, true, None),
+            (0xC000, OAM_DMA_LENGTH + 1, false, None),
+            (0xFFF0, 0x00, true, None),
+            (0xC000, 0x00, false, Some(0xFFF0)),
+        ] {
+            assert!(matches!(
+                load(source, index, active, pending),
+                Err(Error::State(StateError::Corrupt))
+            ));
+        }
+    }
+}
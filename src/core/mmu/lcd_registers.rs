@@ -1,3 +1,6 @@
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::Result;
+
 // LCD 寄存器結構體
 #[derive(Debug, Default, Clone)]
 pub struct LCDRegisters {
@@ -13,6 +16,7 @@ pub struct LCDRegisters {
     pub obp1: u8,     // Object Palette 1 Data
     pub wy: u8,       // Window Y Position
     pub wx: u8,       // Window X Position minus 7
+    stat_line: bool,  // STAT 中斷線 (各來源 OR 的結果)
 }
 
 impl LCDRegisters {
@@ -30,6 +34,91 @@ impl LCDRegisters {
             obp1: 0xE4,  // Default OBJ palette 1
             wy: 0x00,    // Window Y = 0
             wx: 0x00,    // Window X = 0
+            stat_line: false,
+        }
+    }
+
+    /// 目前的 PPU 模式 (STAT bit 0-1)
+    pub fn mode(&self) -> u8 {
+        self.stat & 0x03
+    }
+
+    /// 讀取 STAT：bit 7 固定為 1
+    pub fn read_stat(&self) -> u8 {
+        self.stat | 0x80
+    }
+
+    /// 寫入 STAT：只有 bit 3-6 的中斷啟用位可寫，模式與 LYC=LY 旗標為唯讀
+    pub fn write_stat(&mut self, value: u8) {
+        self.stat = (self.stat & 0x07) | (value & 0x78);
+    }
+
+    /// 由 PPU 更新模式與 LY，回傳是否需要請求 STAT 中斷
+    pub fn update_status(&mut self, mode: u8, ly: u8) -> bool {
+        self.stat = (self.stat & !0x03) | (mode & 0x03);
+        self.ly = ly;
+        self.check_stat_interrupt()
+    }
+
+    /// LCD 關閉時 LY 歸零、模式回到 0，且不產生 STAT 中斷
+    pub fn reset_status(&mut self) {
+        self.stat &= !0x03;
+        self.ly = 0;
+        self.stat_line = false;
+    }
+
+    /// 重新比較 LYC 與 LY 並評估 STAT 中斷線
+    ///
+    /// 四個來源 OR 成單一中斷線，只有在上升沿時才請求中斷，
+    /// 因此某個來源仍維持為真時，其他來源不會再次觸發 (STAT blocking)
+    pub fn check_stat_interrupt(&mut self) -> bool {
+        if self.ly == self.lyc {
+            self.stat |= 0x04;
+        } else {
+            self.stat &= !0x04;
+        }
+
+        let mode = self.mode();
+        let line = (self.stat & 0x40 != 0 && self.stat & 0x04 != 0) // LYC=LY
+            || (self.stat & 0x20 != 0 && mode == 2) // OAM 掃描
+            || (self.stat & 0x10 != 0 && mode == 1) // V-Blank
+            || (self.stat & 0x08 != 0 && mode == 0); // H-Blank
+
+        let rising = line && !self.stat_line;
+        self.stat_line = line;
+        rising
+    }
+}
+
+impl SaveState for LCDRegisters {
+    fn save_state(&self, w: &mut StateWriter) {
+        for value in [
+            self.lcdc, self.stat, self.scy, self.scx, self.ly, self.lyc, self.dma, self.bgp,
+            self.obp0, self.obp1, self.wy, self.wx,
+        ] {
+            w.u8(value);
+        }
+        w.bool(self.stat_line);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        for value in [
+            &mut self.lcdc,
+            &mut self.stat,
+            &mut self.scy,
+            &mut self.scx,
+            &mut self.ly,
+            &mut self.lyc,
+            &mut self.dma,
+            &mut self.bgp,
+            &mut self.obp0,
+            &mut self.obp1,
+            &mut self.wy,
+            &mut self.wx,
+        ] {
+            *value = r.u8()?;
         }
+        self.stat_line = r.bool()?;
+        Ok(())
     }
 }
@@ -1,4 +1,6 @@
 use super::MBCController;
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::Result;
 
 #[derive(Debug)]
 pub struct MBC1 {
@@ -88,6 +90,9 @@ impl MBCController for MBC1 {
                 }
                 self.mode = new_mode;
             }
+            0xA000..=0xBFFF => {
+                // 外部 RAM 由 MMU 處理
+            }
             _ => {
                 log::warn!("MBC1: 嘗試寫入無效位址 {:04X} = {:02X}", addr, value);
             }
@@ -99,11 +104,14 @@ impl MBCController for MBC1 {
     fn translate_rom_address(&self, addr: u16) -> u32 {
         match addr {
             0x0000..=0x3FFF => {
-                log::trace!("MBC1 訪問 ROM Bank 0: {:04X}", addr);
-                addr as u32
+                // RAM 模式下，0x4000-0x5FFF 暫存器也作用於 bank 0 區域 (大容量卡帶)
+                let bank = if self.mode { self.ram_bank << 5 } else { 0 };
+                log::trace!("MBC1 訪問 ROM Bank {}: {:04X}", bank, addr);
+                ((bank * 0x4000) + addr as usize) as u32
             }
             0x4000..=0x7FFF => {
-                let bank = self.rom_bank;
+                // 0x4000-0x5FFF 暫存器提供 ROM bank 號碼的第 5-6 位
+                let bank = (self.ram_bank << 5) | self.rom_bank;
                 let physical_addr = ((bank * 0x4000) + (addr as usize - 0x4000)) as u32;
                 log::trace!(
                     "MBC1 訪問 ROM Bank {}: 邏輯位址={:04X} -> 物理位址={:06X}",
@@ -120,29 +128,49 @@ impl MBCController for MBC1 {
         }
     }
 
-    fn translate_ram_address(&self, addr: u16) -> u16 {
+    fn translate_ram_address(&self, addr: u16) -> Option<u32> {
         if !self.ram_enabled {
-            log::warn!("MBC1: 嘗試訪問已禁用的 RAM {:04X}", addr);
-            return addr;
+            log::trace!("MBC1: 嘗試訪問已禁用的 RAM {:04X}", addr);
+            return None;
         }
 
-        let physical_addr = if self.mode {
-            let addr = ((self.ram_bank * 0x2000) + (addr as usize)) as u16;
-            log::trace!(
-                "MBC1 訪問 RAM Bank {}: 邏輯位址={:04X} -> 物理位址={:04X}",
-                self.ram_bank,
-                addr,
-                addr
-            );
-            addr
-        } else {
-            log::trace!("MBC1 訪問 RAM Bank 0: {:04X}", addr);
-            addr
-        };
-        physical_addr
+        // ROM 模式下固定使用 RAM Bank 0
+        let bank = if self.mode { self.ram_bank } else { 0 };
+        let physical_addr = ((bank * 0x2000) + addr as usize) as u32;
+        log::trace!(
+            "MBC1 訪問 RAM Bank {}: 邏輯位址={:04X} -> 物理位址={:05X}",
+            bank,
+            addr,
+            physical_addr
+        );
+        Some(physical_addr)
     }
 
     fn current_rom_bank(&self) -> u8 {
-        self.rom_bank as u8
+        ((self.ram_bank << 5) | self.rom_bank) as u8
+    }
+
+    fn reset_banking(&mut self) {
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.mode = false;
+    }
+}
+
+impl SaveState for MBC1 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.ram_enabled);
+        w.u16(self.rom_bank as u16);
+        w.u8(self.ram_bank as u8);
+        w.bool(self.mode);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.ram_enabled = r.bool()?;
+        self.rom_bank = r.u16()? as usize;
+        self.ram_bank = r.u8()? as usize;
+        self.mode = r.bool()?;
+        Ok(())
     }
 }
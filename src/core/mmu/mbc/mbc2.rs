@@ -1,4 +1,6 @@
 use super::MBCController;
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::Result;
 
 #[derive(Debug)]
 pub struct MBC2 {
@@ -17,18 +19,16 @@ impl MBC2 {
 
 impl MBCController for MBC2 {
     fn read(&self, _addr: u16) -> u8 {
-        0 // 簡化實現
+        0xFF // 內建 RAM 由 MMU 處理，RAM 禁用時讀取為 0xFF
     }
 
     fn write(&mut self, addr: u16, value: u8) {
         match addr {
-            0x0000..=0x1FFF => {
-                // MBC2 的特殊RAM啟用邏輯
-                if (addr & 0x0100) == 0 {
-                    self.ram_enabled = value & 0x0F == 0x0A;
-                }
+            // MBC2 以位址的 bit 8 區分暫存器：0 = RAM 啟用，1 = ROM bank
+            0x0000..=0x3FFF if (addr & 0x0100) == 0 => {
+                self.ram_enabled = value & 0x0F == 0x0A;
             }
-            0x2000..=0x3FFF => {
+            0x0000..=0x3FFF => {
                 // MBC2 只使用低4位
                 let bank = value & 0x0F;
                 self.rom_bank = if bank == 0 { 1 } else { bank as usize };
@@ -48,15 +48,32 @@ impl MBCController for MBC2 {
         }
     }
 
-    fn translate_ram_address(&self, addr: u16) -> u16 {
+    fn translate_ram_address(&self, addr: u16) -> Option<u32> {
         if !self.ram_enabled {
-            return addr;
+            return None;
         }
-        // MBC2 有512字節的內建RAM
-        (addr & 0x1FF) as u16
+        // MBC2 有512個半字節的內建RAM，在 0xA000-0xBFFF 內重複映射
+        Some((addr & 0x1FF) as u32)
     }
 
     fn current_rom_bank(&self) -> u8 {
         self.rom_bank as u8
     }
+
+    fn reset_banking(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl SaveState for MBC2 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.ram_enabled);
+        w.u16(self.rom_bank as u16);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.ram_enabled = r.bool()?;
+        self.rom_bank = r.u16()? as usize;
+        Ok(())
+    }
 }
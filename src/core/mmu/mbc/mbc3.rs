@@ -1,11 +1,16 @@
+use super::rtc::{ClockSource, Rtc};
 use super::MBCController;
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::{Result, StateError};
 
 #[derive(Debug)]
 pub struct MBC3 {
     ram_enabled: bool,
     rom_bank: usize,
     ram_bank: usize,
-    rtc_enabled: bool,
+    rtc_enabled: bool, // 0xA000-0xBFFF 映射到 RTC 暫存器
+    rtc_select: u8,    // 選擇的 RTC 暫存器 (08h-0Ch)
+    rtc: Option<Rtc>,  // 只有 MBC3+TIMER 卡帶有時鐘
 }
 
 impl MBC3 {
@@ -15,13 +20,27 @@ impl MBC3 {
             rom_bank: 1,
             ram_bank: 0,
             rtc_enabled: false,
+            rtc_select: 0x08,
+            rtc: None,
+        }
+    }
+
+    /// 建立帶有即時時鐘的 MBC3，時間來源可替換以便測試
+    pub fn with_rtc(clock: Box<dyn ClockSource>) -> Self {
+        MBC3 {
+            rtc: Some(Rtc::new(clock)),
+            ..Self::new()
         }
     }
 }
 
 impl MBCController for MBC3 {
     fn read(&self, _addr: u16) -> u8 {
-        0 // 簡化實現
+        // 外部 RAM 由 MMU 處理，這裡只負責 RTC 暫存器
+        match &self.rtc {
+            Some(rtc) if self.ram_enabled && self.rtc_enabled => rtc.read(self.rtc_select),
+            _ => 0xFF,
+        }
     }
 
     fn write(&mut self, addr: u16, value: u8) {
@@ -38,8 +57,21 @@ impl MBCController for MBC3 {
                 if value <= 0x03 {
                     self.ram_bank = value as usize;
                     self.rtc_enabled = false;
-                } else if value >= 0x08 && value <= 0x0C {
+                } else if (0x08..=0x0C).contains(&value) {
                     self.rtc_enabled = true;
+                    self.rtc_select = value;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write_latch(value);
+                }
+            }
+            0xA000..=0xBFFF => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    if self.ram_enabled && self.rtc_enabled {
+                        rtc.write(self.rtc_select, value);
+                    }
                 }
             }
             _ => {}
@@ -57,14 +89,68 @@ impl MBCController for MBC3 {
         }
     }
 
-    fn translate_ram_address(&self, addr: u16) -> u16 {
+    fn translate_ram_address(&self, addr: u16) -> Option<u32> {
         if !self.ram_enabled || self.rtc_enabled {
-            return addr;
+            return None;
         }
-        ((self.ram_bank * 0x2000) + (addr as usize)) as u16
+        Some(((self.ram_bank * 0x2000) + (addr as usize)) as u32)
     }
 
     fn current_rom_bank(&self) -> u8 {
         self.rom_bank as u8
     }
+
+    fn reset_banking(&mut self) {
+        // RTC 由電池供電，重置後時間不變
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.rtc_enabled = false;
+        self.rtc_select = 0x08;
+    }
+
+    fn step(&mut self, cycles: u32) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.step(cycles);
+        }
+    }
+
+    fn save_footer(&self) -> Option<Vec<u8>> {
+        self.rtc.as_ref().map(|rtc| rtc.to_footer())
+    }
+
+    fn load_footer(&mut self, footer: &[u8]) -> bool {
+        match self.rtc.as_mut() {
+            Some(rtc) => rtc.load_footer(footer),
+            None => false,
+        }
+    }
+}
+
+impl SaveState for MBC3 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.ram_enabled);
+        w.u16(self.rom_bank as u16);
+        w.u8(self.ram_bank as u8);
+        w.bool(self.rtc_enabled);
+        w.u8(self.rtc_select);
+        w.bool(self.rtc.is_some());
+        if let Some(rtc) = &self.rtc {
+            rtc.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.ram_enabled = r.bool()?;
+        self.rom_bank = r.u16()? as usize;
+        self.ram_bank = r.u8()? as usize;
+        self.rtc_enabled = r.bool()?;
+        self.rtc_select = r.u8()?;
+        // 同一卡帶的時鐘有無不會改變
+        match (r.bool()?, self.rtc.as_mut()) {
+            (true, Some(rtc)) => rtc.load_state(r),
+            (false, None) => Ok(()),
+            _ => Err(StateError::Corrupt.into()),
+        }
+    }
 }
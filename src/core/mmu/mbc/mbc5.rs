@@ -1,4 +1,6 @@
 use super::MBCController;
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::Result;
 
 #[derive(Debug)]
 pub struct MBC5 {
@@ -19,7 +21,7 @@ impl MBC5 {
 
 impl MBCController for MBC5 {
     fn read(&self, _addr: u16) -> u8 {
-        0 // 簡化實現
+        0xFF // 外部 RAM 由 MMU 處理
     }
 
     fn write(&mut self, addr: u16, value: u8) {
@@ -53,14 +55,33 @@ impl MBCController for MBC5 {
         }
     }
 
-    fn translate_ram_address(&self, addr: u16) -> u16 {
+    fn translate_ram_address(&self, addr: u16) -> Option<u32> {
         if !self.ram_enabled {
-            return addr;
+            return None;
         }
-        ((self.ram_bank * 0x2000) + (addr as usize)) as u16
+        Some(((self.ram_bank * 0x2000) + (addr as usize)) as u32)
     }
 
     fn current_rom_bank(&self) -> u8 {
         (self.rom_bank & 0xFF) as u8
     }
+
+    fn reset_banking(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl SaveState for MBC5 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.ram_enabled);
+        w.u16(self.rom_bank as u16);
+        w.u8(self.ram_bank as u8);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.ram_enabled = r.bool()?;
+        self.rom_bank = r.u16()? as usize;
+        self.ram_bank = r.u8()? as usize;
+        Ok(())
+    }
 }
@@ -2,34 +2,55 @@ pub mod mbc1;
 pub mod mbc2;
 pub mod mbc3;
 pub mod mbc5;
+pub mod rtc;
 pub mod types;
 
 pub use self::mbc1::MBC1;
 pub use self::mbc2::MBC2;
 pub use self::mbc3::MBC3;
 pub use self::mbc5::MBC5;
+pub use self::rtc::SystemClock;
 #[allow(unused_imports)]
 pub use self::types::MemoryBankController;
 
-/// MBC 控制器特徵
-#[allow(dead_code)]
-pub trait MBCController: std::fmt::Debug {
-    #[allow(dead_code)]
+use crate::core::state::SaveState;
+
+/// MBC 控制器特徵，切換 bank 的暫存器狀態隨即時存檔保存
+pub trait MBCController: std::fmt::Debug + Send + SaveState {
+    /// 讀取 0xA000-0xBFFF 區域
     fn read(&self, addr: u16) -> u8;
+    /// 寫入 0x0000-0x7FFF 控制暫存器或 0xA000-0xBFFF 區域
     fn write(&mut self, addr: u16, value: u8);
     fn translate_rom_address(&self, addr: u16) -> u32;
-    fn translate_ram_address(&self, addr: u16) -> u16;
+    /// 將 0xA000-0xBFFF 的偏移轉為外部 RAM 位址；RAM 禁用或映射到其他暫存器時回傳 None
+    fn translate_ram_address(&self, addr: u16) -> Option<u32>;
     fn current_rom_bank(&self) -> u8;
+    /// 主機重置：bank 暫存器回到開機狀態，卡帶上的時鐘等電池供電硬體保持不變
+    fn reset_banking(&mut self);
+
+    /// 推進卡帶上的時鐘等硬體
+    fn step(&mut self, _cycles: u32) {}
+    /// 附加在存檔尾端的狀態 (例如 MBC3 RTC)
+    fn save_footer(&self) -> Option<Vec<u8>> {
+        None
+    }
+    /// 從存檔尾端還原狀態，格式不符時回傳 false
+    fn load_footer(&mut self, _footer: &[u8]) -> bool {
+        false
+    }
 }
 
 // 用於創建適當的 MBC 實例
 pub fn create_mbc(cartridge_type: u8) -> Option<Box<dyn MBCController>> {
     match cartridge_type {
-        0x00 => None, // ROM ONLY
+        0x00 => None,                               // ROM ONLY
+        0x08..=0x09 => None,                        // ROM+RAM，無 MBC
         0x01..=0x03 => Some(Box::new(MBC1::new())), // MBC1
         0x05..=0x06 => Some(Box::new(MBC2::new())), // MBC2
-        0x0F..=0x13 => Some(Box::new(MBC3::new())), // MBC3
+        0x11..=0x13 => Some(Box::new(MBC3::new())), // MBC3
         0x19..=0x1E => Some(Box::new(MBC5::new())), // MBC5
+        // MBC3+TIMER 帶有即時時鐘
+        0x0F..=0x10 => Some(Box::new(MBC3::with_rtc(Box::new(SystemClock)))),
         _ => None,
     }
 }
@@ -0,0 +1,288 @@
+use crate::core::cycles::{CyclesType, CPU_CLOCK};
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 存檔尾端 RTC 區塊的大小 (VBA-M / BGB 格式)
+pub const RTC_FOOTER_SIZE: usize = 48;
+
+/// DH 暫存器位元
+const DH_DAY_HIGH: u8 = 0x01;
+const DH_HALT: u8 = 0x40;
+const DH_CARRY: u8 = 0x80;
+
+/// 牆上時鐘來源，以 UNIX 秒數表示
+pub trait ClockSource: std::fmt::Debug + Send {
+    fn now(&self) -> u64;
+}
+
+/// 使用系統時間的時鐘來源
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl ClockSource for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// MBC3 即時時鐘
+///
+/// 暫存器順序為 S, M, H, DL, DH，對應 RAM bank 選擇值 08h-0Ch。
+/// 時鐘隨模擬週期前進；關機期間經過的時間在載入存檔時以牆上時鐘補上。
+#[derive(Debug)]
+pub struct Rtc {
+    registers: [u8; 5],
+    latched: [u8; 5],
+    latch_armed: bool, // 上次寫入 0x6000 的值為 0x00
+    sub_cycles: CyclesType,
+    clock: Box<dyn ClockSource>,
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn ClockSource>) -> Self {
+        Rtc {
+            registers: [0; 5],
+            latched: [0; 5],
+            latch_armed: false,
+            sub_cycles: 0,
+            clock,
+        }
+    }
+
+    /// 依模擬的 CPU 週期推進時鐘
+    pub fn step(&mut self, cycles: CyclesType) {
+        if self.halted() {
+            return;
+        }
+
+        self.sub_cycles += cycles;
+        while self.sub_cycles >= CPU_CLOCK {
+            self.sub_cycles -= CPU_CLOCK;
+            self.tick_second();
+        }
+    }
+
+    /// 寫入 0x6000-0x7FFF：先寫 0x00 再寫 0x01 時鎖存目前時間
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.latched = self.registers;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    /// 讀取已鎖存的暫存器 (select 為 08h-0Ch)
+    pub fn read(&self, select: u8) -> u8 {
+        match select {
+            0x08 => self.latched[0] & 0x3F,
+            0x09 => self.latched[1] & 0x3F,
+            0x0A => self.latched[2] & 0x1F,
+            0x0B => self.latched[3],
+            0x0C => self.latched[4] & (DH_CARRY | DH_HALT | DH_DAY_HIGH),
+            _ => 0xFF,
+        }
+    }
+
+    /// 寫入即時暫存器 (select 為 08h-0Ch)
+    pub fn write(&mut self, select: u8, value: u8) {
+        match select {
+            0x08 => {
+                self.registers[0] = value & 0x3F;
+                // 寫入秒數會重設內部的秒內計數器
+                self.sub_cycles = 0;
+            }
+            0x09 => self.registers[1] = value & 0x3F,
+            0x0A => self.registers[2] = value & 0x1F,
+            0x0B => self.registers[3] = value,
+            0x0C => self.registers[4] = value & (DH_CARRY | DH_HALT | DH_DAY_HIGH),
+            _ => {}
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.registers[4] & DH_HALT != 0
+    }
+
+    fn days(&self) -> u16 {
+        (((self.registers[4] & DH_DAY_HIGH) as u16) << 8) | self.registers[3] as u16
+    }
+
+    fn set_days(&mut self, days: u16) {
+        self.registers[3] = days as u8;
+        self.registers[4] = (self.registers[4] & !DH_DAY_HIGH) | ((days >> 8) as u8 & DH_DAY_HIGH);
+    }
+
+    fn tick_second(&mut self) {
+        // 超出範圍的值會在 6/5 位元溢位時歸零，但不進位，與硬體相同
+        self.registers[0] = (self.registers[0] + 1) & 0x3F;
+        if self.registers[0] != 60 {
+            return;
+        }
+        self.registers[0] = 0;
+
+        self.registers[1] = (self.registers[1] + 1) & 0x3F;
+        if self.registers[1] != 60 {
+            return;
+        }
+        self.registers[1] = 0;
+
+        self.registers[2] = (self.registers[2] + 1) & 0x1F;
+        if self.registers[2] != 24 {
+            return;
+        }
+        self.registers[2] = 0;
+
+        let days = self.days() + 1;
+        if days > 0x1FF {
+            self.registers[4] |= DH_CARRY;
+        }
+        self.set_days(days & 0x1FF);
+    }
+
+    /// 推進整數秒，用於補上模擬器關閉期間經過的時間
+    pub fn advance_seconds(&mut self, mut seconds: u64) {
+        if self.halted() {
+            return;
+        }
+
+        // 有超出範圍的暫存器時逐秒推進，直到回到正常範圍
+        while seconds > 0
+            && (self.registers[0] >= 60 || self.registers[1] >= 60 || self.registers[2] >= 24)
+        {
+            self.tick_second();
+            seconds -= 1;
+        }
+
+        let total = self.days() as u64 * 86_400
+            + self.registers[2] as u64 * 3_600
+            + self.registers[1] as u64 * 60
+            + self.registers[0] as u64
+            + seconds;
+
+        let days = total / 86_400;
+        if days > 0x1FF {
+            self.registers[4] |= DH_CARRY;
+        }
+        self.registers[0] = (total % 60) as u8;
+        self.registers[1] = (total / 60 % 60) as u8;
+        self.registers[2] = (total / 3_600 % 24) as u8;
+        self.set_days((days % 0x200) as u16);
+    }
+
+    /// 產生存檔尾端的 48 位元組 RTC 區塊
+    ///
+    /// 格式：即時暫存器與鎖存暫存器各 5 個 32 位元小端序值，接著 64 位元 UNIX 時間戳
+    pub fn to_footer(&self) -> Vec<u8> {
+        let mut footer = Vec::with_capacity(RTC_FOOTER_SIZE);
+        for &value in self.registers.iter().chain(self.latched.iter()) {
+            footer.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        footer.extend_from_slice(&self.clock.now().to_le_bytes());
+        footer
+    }
+
+    /// 從存檔尾端載入 RTC 狀態，並補上存檔後經過的時間
+    ///
+    /// 也接受使用 32 位元時間戳的 44 位元組舊格式
+    pub fn load_footer(&mut self, footer: &[u8]) -> bool {
+        let timestamp = match footer.len() {
+            48 => u64::from_le_bytes(footer[40..48].try_into().unwrap_or_default()),
+            44 => u32::from_le_bytes(footer[40..44].try_into().unwrap_or_default()) as u64,
+            _ => return false,
+        };
+
+        let value_at = |i: usize| footer[i * 4];
+        for i in 0..5 {
+            self.registers[i] = value_at(i);
+            self.latched[i] = value_at(i + 5);
+        }
+        self.registers[4] &= DH_CARRY | DH_HALT | DH_DAY_HIGH;
+        self.sub_cycles = 0;
+
+        let elapsed = self.clock.now().saturating_sub(timestamp);
+        self.advance_seconds(elapsed);
+        true
+    }
+}
+
+// 即時存檔只保存暫存器，不像 .sav 尾端那樣以牆上時鐘補上經過的時間
+impl SaveState for Rtc {
+    fn save_state(&self, w: &mut StateWriter) {
+        for &value in self.registers.iter().chain(self.latched.iter()) {
+            w.u8(value);
+        }
+        w.bool(self.latch_armed);
+        w.u32(self.sub_cycles);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        for value in self.registers.iter_mut().chain(self.latched.iter_mut()) {
+            *value = r.u8()?;
+        }
+        self.latch_armed = r.bool()?;
+        self.sub_cycles = r.u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct FixedClock(Arc<AtomicU64>);
+
+    impl ClockSource for FixedClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    fn latched(rtc: &mut Rtc) -> [u8; 5] {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        [0x08, 0x09, 0x0A, 0x0B, 0x0C].map(|select| rtc.read(select))
+    }
+
+    #[test]
+    fn test_rtc_ticks_in_emulated_time() {
+        let mut rtc = Rtc::new(Box::new(SystemClock));
+        rtc.write(0x08, 59);
+        rtc.write(0x09, 59);
+        rtc.write(0x0A, 23);
+        rtc.write(0x0B, 0xFF);
+        rtc.write(0x0C, 0x01);
+
+        rtc.step(CPU_CLOCK - 4);
+        assert_eq!(latched(&mut rtc), [59, 59, 23, 0xFF, 0x01]);
+
+        // Day counter overflows and sets the carry bit
+        rtc.step(4);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, DH_CARRY]);
+
+        // Halted clock doesn't advance
+        rtc.write(0x0C, DH_HALT);
+        rtc.step(CPU_CLOCK * 3);
+        assert_eq!(latched(&mut rtc)[0], 0);
+    }
+
+    #[test]
+    fn test_rtc_footer_catches_up_wall_clock() {
+        let now = Arc::new(AtomicU64::new(1_000_000));
+        let mut rtc = Rtc::new(Box::new(FixedClock(now.clone())));
+        rtc.write(0x09, 30);
+        let footer = rtc.to_footer();
+        assert_eq!(footer.len(), RTC_FOOTER_SIZE);
+
+        // Two days, one hour and five seconds later
+        now.store(1_000_000 + 2 * 86_400 + 3_600 + 5, Ordering::Relaxed);
+        let mut restored = Rtc::new(Box::new(FixedClock(now)));
+        assert!(restored.load_footer(&footer));
+        assert_eq!(latched(&mut restored), [5, 30, 1, 2, 0]);
+    }
+}
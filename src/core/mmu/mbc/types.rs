@@ -13,7 +13,7 @@ pub enum MBCType {
 impl MBCType {
     pub fn from_cartridge_type(cartridge_type: u8) -> Self {
         match cartridge_type {
-            0x00 => MBCType::None,
+            0x00 | 0x08 | 0x09 => MBCType::None,
             0x01..=0x03 => MBCType::MBC1,
             0x05..=0x06 => MBCType::MBC2,
             0x0F..=0x13 => MBCType::MBC3,
@@ -34,6 +34,14 @@ impl MBCType {
     }
 }
 
+/// 卡帶類型是否帶有電池 (RAM 內容需要存檔)
+pub fn has_battery(cartridge_type: u8) -> bool {
+    matches!(
+        cartridge_type,
+        0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFF
+    )
+}
+
 #[allow(dead_code)]
 pub fn get_rom_size_bytes(rom_size_code: u8) -> usize {
     match rom_size_code {
@@ -92,6 +100,15 @@ impl MBCState {
             battery_backed: false,
         }
     }
+
+    /// 依卡帶標頭 0x147 的類型建立狀態
+    pub fn from_cartridge_type(cartridge_type: u8) -> Self {
+        Self {
+            rtc_enabled: matches!(cartridge_type, 0x0F | 0x10),
+            battery_backed: has_battery(cartridge_type),
+            ..Self::new(MBCType::from_cartridge_type(cartridge_type))
+        }
+    }
 }
 
 #[allow(dead_code)]
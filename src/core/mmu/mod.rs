@@ -1,47 +1,74 @@
-use crate::error::{Error, HardwareError, Result};
+use crate::core::bus::Bus;
+use crate::core::cpu::interrupts::Interrupt;
+use crate::core::cycles::{CyclesType, Tick};
+use crate::core::serial::{Serial, SerialLink};
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::core::timer::Timer;
+use crate::error::{Result, StateError};
 use crate::interface::input::joypad::Joypad;
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::path::PathBuf;
 
+pub mod boot;
+pub mod dma;
 pub mod lcd_registers;
 pub mod mbc;
+pub mod save;
 
+use boot::{BootRom, Model};
+use dma::Dma;
 use lcd_registers::LCDRegisters;
+use mbc::types::{MBCState, MBCType};
+use mbc::MBCController;
+use save::SaveFile;
 
-/// Nintendo Logo used for ROM validation
-const NINTENDO_LOGO: &[u8; 48] = &[
-    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
-    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
-    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+/// Bits of NR10-NR52 and the unused 0xFF27-0xFF2F that always read as 1
+const SOUND_READ_MASK: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+/// NR10-NR52 after the boot ROM played its chime, the same on every model
+const POST_BOOT_SOUND: [u8; 0x17] = [
+    0x80, 0xBF, 0xF3, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x77, 0xF3, 0xF1, // NR50-NR52
 ];
 
 /// Game Boy Memory Management Unit (MMU)
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct MMU {
     pub cartridge_rom: Vec<u8>,
-    pub work_ram: [u8; 0x2000],              // 8KB work RAM
-    pub high_ram: [u8; 0x80],                // 128 bytes high RAM
-    pub video_ram: [u8; 0x2000],             // 8KB video RAM
+    boot_rom: Option<BootRom>, // Overlaid on the cartridge while mapped
+    boot_rom_mapped: bool,     // Cleared by writing 0xFF50
+    model: Model,
+    mbc: Option<Box<dyn MBCController>>, // Bank controller from header 0x147
+    mbc_state: MBCState,                 // Cartridge type and battery flag
+    pub external_ram: Vec<u8>,           // Cartridge RAM (0xA000-0xBFFF)
+    save_file: Option<SaveFile>,         // .sav backing for battery-backed RAM
+    pub work_ram: [u8; 0x2000],          // 8KB work RAM
+    pub high_ram: [u8; 0x80],            // 128 bytes high RAM
+    pub video_ram: [u8; 0x2000],         // 8KB video RAM
     pub object_attribute_memory: [u8; 0xA0], // Sprite Attribute Table
-    pub io_registers: [u8; 0x80],            // I/O registers
-    pub interrupt_enable: u8,                // 0xFFFF
-    pub interrupt_flags: u8,                 // 0xFF0F
+    pub io_registers: [u8; 0x80],        // I/O registers
+    pub interrupt_enable: u8,            // 0xFFFF
+    pub interrupt_flags: u8,             // 0xFF0F
     pub lcd_registers: LCDRegisters,
-    pub ly: u8,           // Current scanline
-    pub lyc: u8,          // LY Compare
+    pub serial: Serial,   // SB/SC (0xFF01-0xFF02)
+    pub timer: Timer,     // DIV/TIMA/TMA/TAC (0xFF04-0xFF07)
+    dma: Dma,             // OAM DMA engine (0xFF46)
+    joypad_keys: u8,      // Directions (high nibble) and buttons (low nibble), 0 = pressed
     pub instance_id: u64, // Used to identify different MMU instances
 }
 
 impl MMU {
     pub fn new() -> Self {
-        println!("MMU::new() - Initialization started");
-        if let Ok(mut file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("logs/mmu_init.log")
-        {
-            let _ = writeln!(file, "[INFO] MMU::new() - Initialization started");
-        }
+        log::trace!("MMU::new() - Initialization started");
 
         let instance_id = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -50,6 +77,13 @@ impl MMU {
 
         let mut mmu = Self {
             cartridge_rom: Vec::new(),
+            boot_rom: None,
+            boot_rom_mapped: false,
+            model: Model::Dmg,
+            mbc: None,
+            mbc_state: MBCState::new(MBCType::None),
+            external_ram: Vec::new(),
+            save_file: None,
             work_ram: [0; 0x2000],
             high_ram: [0; 0x80],
             video_ram: [0; 0x2000],
@@ -58,62 +92,142 @@ impl MMU {
             interrupt_enable: 0,
             interrupt_flags: 0,
             lcd_registers: LCDRegisters::new(),
-            ly: 0,
-            lyc: 0,
+            serial: Serial::new(),
+            timer: Timer::new(),
+            dma: Dma::new(),
+            joypad_keys: 0xFF,
             instance_id,
         };
 
         // Initialize basic graphics data into VRAM
         mmu.init_default_graphics();
-        println!("MMU::new() - Initialization completed");
+        log::trace!("MMU::new() - Initialization completed");
         mmu
     }
 
     pub fn read_byte(&self, address: u16) -> Result<u8> {
-        let value = match address {
+        // While OAM DMA owns the bus only HRAM and the I/O registers respond
+        if self.dma.is_active() && address < 0xFF00 {
+            return Ok(0xFF);
+        }
+        if self.locked_by_ppu(address) {
+            return Ok(0xFF);
+        }
+
+        let value = self.peek(address)?;
+
+        if (0x8000..=0x9FFF).contains(&address) {
+            log::trace!(
+                "VRAM Read - Address: 0x{:04X}, Value: 0x{:02X}",
+                address,
+                value
+            );
+        }
+
+        Ok(value)
+    }
+
+    /// Read what is at `address` without side effects, ignoring the bus lock
+    /// OAM DMA holds. Tracers and debuggers see the real memory contents.
+    pub fn peek(&self, address: u16) -> Result<u8> {
+        Ok(match address {
             // ROM area (0x0000-0x7FFF)
-            0x0000..=0x7FFF => {
-                if address as usize >= self.cartridge_rom.len() {
-                    0xFF
-                } else {
-                    self.cartridge_rom[address as usize]
-                }
-            }
+            0x0000..=0x7FFF => self
+                .read_boot_rom(address)
+                .unwrap_or_else(|| self.read_rom(address)),
             0x8000..=0x9FFF => self.video_ram[address as usize - 0x8000],
-            0xA000..=0xBFFF => 0xFF, // External RAM (not implemented yet)
+            0xA000..=0xBFFF => self.read_external_ram(address),
             0xC000..=0xDFFF => self.work_ram[address as usize - 0xC000],
             0xE000..=0xFDFF => self.work_ram[address as usize - 0xE000], // Echo RAM
             0xFE00..=0xFE9F => self.object_attribute_memory[address as usize - 0xFE00],
             0xFEA0..=0xFEFF => 0xFF, // Unused area
             0xFF00..=0xFFFF => self.read_io(address)?,
+        })
+    }
+
+    /// Read from cartridge ROM, letting the bank controller map 0x4000-0x7FFF
+    fn read_rom(&self, address: u16) -> u8 {
+        if self.cartridge_rom.is_empty() {
+            return 0xFF;
+        }
+
+        let offset = match &self.mbc {
+            Some(mbc) => mbc.translate_rom_address(address) as usize,
+            None => address as usize,
         };
 
-        // Log VRAM read
-        if (0x8000..=0x9FFF).contains(&address) {
-            if let Ok(mut file) = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("logs/vram_read.log")
-            {
-                let _ = writeln!(
-                    file,
-                    "VRAM Read - Address: 0x{:04X}, Value: 0x{:02X}",
-                    address, value
-                );
-            }
+        // Bank numbers larger than the ROM wrap around, as on real cartridges
+        self.cartridge_rom[offset % self.cartridge_rom.len()]
+    }
+
+    /// Read cartridge RAM, honouring the bank controller's enable latch and RAM banking
+    fn read_external_ram(&self, address: u16) -> u8 {
+        let offset = match &self.mbc {
+            Some(mbc) => match mbc.translate_ram_address(address - 0xA000) {
+                Some(offset) => offset as usize,
+                // RAM disabled or a controller register is mapped here instead
+                None => return mbc.read(address),
+            },
+            None => (address - 0xA000) as usize,
+        };
+
+        if self.external_ram.is_empty() {
+            return 0xFF;
         }
 
-        Ok(value)
+        let value = self.external_ram[offset % self.external_ram.len()];
+        if self.mbc_state.mbc_type == MBCType::MBC2 {
+            value | 0xF0 // MBC2 RAM is only 4 bits wide
+        } else {
+            value
+        }
+    }
+
+    fn write_external_ram(&mut self, address: u16, value: u8) {
+        let offset = match self.mbc.as_mut() {
+            Some(mbc) => match mbc.translate_ram_address(address - 0xA000) {
+                Some(offset) => offset as usize,
+                None => {
+                    mbc.write(address, value);
+                    return;
+                }
+            },
+            None => (address - 0xA000) as usize,
+        };
+
+        if self.external_ram.is_empty() {
+            return;
+        }
+
+        let len = self.external_ram.len();
+        self.external_ram[offset % len] = if self.mbc_state.mbc_type == MBCType::MBC2 {
+            value & 0x0F
+        } else {
+            value
+        };
+
+        if let Some(save) = self.save_file.as_mut() {
+            save.mark_dirty();
+        }
     }
 
     fn read_io(&self, address: u16) -> Result<u8> {
         let value = match address {
-            0xFF00 => 0xFF,          // Joypad (not implemented yet)
-            0xFF01..=0xFF02 => 0xFF, // Serial transfer (not implemented)
-            0xFF04..=0xFF07 => 0xFF, // Timer (not implemented)
-            0xFF10..=0xFF3F => 0xFF, // Sound (not implemented)
+            0xFF00 => self.joypad_register(),
+            0xFF01..=0xFF02 => self.serial.read_byte(address)?,
+            0xFF04..=0xFF07 => self.timer.read_byte(address)?,
+            // IF: the upper 3 bits always read as 1
+            0xFF0F => self.interrupt_flags | 0xE0,
+            // Sound: no APU behind these yet, so they read back what was
+            // written with the hardware's unreadable bits set
+            0xFF10..=0xFF2F => {
+                let index = (address - 0xFF10) as usize;
+                self.io_registers[0x10 + index] | SOUND_READ_MASK[index]
+            }
+            0xFF30..=0xFF3F => self.io_registers[(address - 0xFF00) as usize], // Wave RAM
             0xFF40..=0xFF4B => self.read_lcd_register(address),
-            0xFF4C..=0xFF7F => self.io_registers[(address - 0xFF00) as usize],
+            // Boot ROM lock is write-only; the CGB registers aren't emulated
+            0xFF4C..=0xFF7F => 0xFF,
             0xFF80..=0xFFFE => self.high_ram[(address - 0xFF80) as usize],
             0xFFFF => self.interrupt_enable,
             _ => 0xFF,
@@ -124,11 +238,12 @@ impl MMU {
     fn read_lcd_register(&self, address: u16) -> u8 {
         match address {
             0xFF40 => self.lcd_registers.lcdc,
-            0xFF41 => self.lcd_registers.stat,
+            0xFF41 => self.lcd_registers.read_stat(),
             0xFF42 => self.lcd_registers.scy,
             0xFF43 => self.lcd_registers.scx,
-            0xFF44 => self.ly,
-            0xFF45 => self.lyc,
+            0xFF44 => self.lcd_registers.ly,
+            0xFF45 => self.lcd_registers.lyc,
+            0xFF46 => self.lcd_registers.dma,
             0xFF47 => self.lcd_registers.bgp,
             0xFF48 => self.lcd_registers.obp0,
             0xFF49 => self.lcd_registers.obp1,
@@ -142,46 +257,46 @@ impl MMU {
         // LCDC bit 7 controls LCD enable/disable
         self.lcd_registers.lcdc & 0x80 != 0
     }
+
+    /// Whether the PPU has `address` to itself in its current mode: VRAM
+    /// during pixel transfer, OAM during OAM scan and pixel transfer
+    fn locked_by_ppu(&self, address: u16) -> bool {
+        if !self.lcd_enabled() {
+            return false;
+        }
+        match address {
+            0x8000..=0x9FFF => self.lcd_registers.mode() == 3,
+            0xFE00..=0xFE9F => matches!(self.lcd_registers.mode(), 2 | 3),
+            _ => false,
+        }
+    }
+
     pub fn write_byte(&mut self, address: u16, value: u8) -> Result<()> {
         // Disable frequent memory write logging for performance
         // Only log critical operations if needed
 
-        match address {
-            // ROM area (read-only), writes will be ignored
-            0x0000..=0x7FFF => Ok(()),
-            0x8000..=0x9FFF => {
-                // Check LCD status
-                let mode = (self.lcd_registers.stat & 0x03) as u8;
-                if mode == 3 {
-                    return Err(Error::Hardware(HardwareError::MemoryWrite(
-                        "Cannot write to VRAM during LCD mode 3".to_string(),
-                    )));
-                }
+        // Writes the bus owner doesn't take are dropped, as on hardware
+        if (self.dma.is_active() && address < 0xFF00) || self.locked_by_ppu(address) {
+            return Ok(());
+        }
 
-                // VRAM write handling
-                if address as usize - 0x8000 < self.video_ram.len() {
-                    // Determine if write is allowed:
-                    // Check if LCD is enabled and in proper mode
-                    let can_write = !self.lcd_enabled() || mode != 3;
-                    if can_write {
-                        self.video_ram[address as usize - 0x8000] = value;
-
-                        // Temporarily enable VRAM logging to debug ROM graphics
-                        if let Ok(mut file) = std::fs::OpenOptions::new()
-                            .create(true)
-                            .append(true)
-                            .open("logs/vram_write.log")
-                        {
-                            writeln!(file, "VRAM Write: [0x{:04X}] = 0x{:02X}", address, value)
-                                .ok();
-                        }
-                    } else {
-                        // VRAM write blocked during mode 3 - don't log for performance
-                    }
+        match address {
+            // ROM area is read-only, writes go to the bank controller registers
+            0x0000..=0x7FFF => {
+                if let Some(mbc) = self.mbc.as_mut() {
+                    mbc.write(address, value);
                 }
                 Ok(())
             }
-            0xA000..=0xBFFF => Ok(()), // External RAM (not implemented yet)
+            0x8000..=0x9FFF => {
+                self.video_ram[address as usize - 0x8000] = value;
+                log::trace!("VRAM Write: [0x{:04X}] = 0x{:02X}", address, value);
+                Ok(())
+            }
+            0xA000..=0xBFFF => {
+                self.write_external_ram(address, value);
+                Ok(())
+            }
             0xC000..=0xDFFF => {
                 self.work_ram[(address - 0xC000) as usize] = value;
                 Ok(())
@@ -212,10 +327,14 @@ impl MMU {
         match address {
             0xFF40 => {
                 self.lcd_registers.lcdc = value;
+                if !self.lcd_enabled() {
+                    self.lcd_registers.reset_status();
+                }
                 Ok(())
             }
             0xFF41 => {
-                self.lcd_registers.stat = value;
+                self.lcd_registers.write_stat(value);
+                self.check_stat_interrupt();
                 Ok(())
             }
             0xFF42 => {
@@ -226,9 +345,47 @@ impl MMU {
                 self.lcd_registers.scx = value;
                 Ok(())
             }
+            0xFF0F => {
+                self.interrupt_flags = value & 0x1F;
+                Ok(())
+            }
+            0xFF00 => {
+                // Only the P14/P15 select bits are writable
+                let old = self.joypad_register();
+                self.io_registers[0x00] = value & 0x30;
+                self.check_joypad_interrupt(old);
+                Ok(())
+            }
+            0xFF01..=0xFF02 => self.serial.write_byte(address, value),
+            0xFF04..=0xFF07 => self.timer.write_byte(address, value),
             0xFF44 => Ok(()), // LY is read-only
+            0xFF26 => {
+                // Only the power bit is writable; turning it off clears every
+                // sound register and stops the channels
+                if value & 0x80 == 0 {
+                    self.io_registers[0x10..=0x26].fill(0);
+                } else {
+                    self.io_registers[0x26] |= 0x80;
+                }
+                Ok(())
+            }
+            // The other sound registers ignore writes while the APU is off
+            0xFF10..=0xFF25 if self.io_registers[0x26] & 0x80 == 0 => Ok(()),
+            0xFF46 => {
+                self.lcd_registers.dma = value;
+                self.dma.start(value);
+                Ok(())
+            }
+            0xFF50 => {
+                // Any non-zero write unmaps the boot ROM until the next reset
+                if value != 0 {
+                    self.boot_rom_mapped = false;
+                }
+                Ok(())
+            }
             0xFF45 => {
-                self.lyc = value;
+                self.lcd_registers.lyc = value;
+                self.check_stat_interrupt();
                 Ok(())
             }
             0xFF47 => {
@@ -279,71 +436,247 @@ impl MMU {
         self.object_attribute_memory.fill(0);
     }
 
+    /// Overlay a boot ROM on the cartridge; it runs on the next `load_rom` or `reset`
+    pub fn load_boot_rom(&mut self, boot_rom: BootRom) {
+        self.model = boot_rom.model();
+        self.boot_rom = Some(boot_rom);
+    }
+
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom_mapped
+    }
+
+    /// Header checksum (0x014D), which decides the post-boot H and C flags
+    pub fn header_checksum(&self) -> u8 {
+        self.cartridge_rom.get(0x14D).copied().unwrap_or(0)
+    }
+
+    /// Cartridge title (0x0134-0x0143) without its zero padding
+    pub fn rom_title(&self) -> String {
+        let title = self.cartridge_rom.get(0x134..0x144).unwrap_or(&[]);
+        String::from_utf8_lossy(title)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    /// Global checksum (0x014E-0x014F, big-endian) over the whole ROM
+    pub fn global_checksum(&self) -> u16 {
+        match self.cartridge_rom.get(0x14E..0x150) {
+            Some(&[high, low]) => u16::from_be_bytes([high, low]),
+            _ => 0,
+        }
+    }
+
+    fn read_boot_rom(&self, address: u16) -> Option<u8> {
+        self.boot_rom
+            .as_ref()
+            .filter(|_| self.boot_rom_mapped)
+            .and_then(|boot_rom| boot_rom.read(address))
+    }
+
     pub fn vram(&self) -> &[u8] {
         &self.video_ram
     }
     pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<()> {
+        // Don't lose unsaved progress of a previously loaded cartridge
+        self.flush_save()?;
+        self.save_file = None;
+
         self.cartridge_rom = rom_data.to_vec();
+        self.mbc = self.create_cartridge_mbc();
+        self.mbc_state =
+            MBCState::from_cartridge_type(self.cartridge_rom.get(0x147).copied().unwrap_or(0));
+        self.external_ram = vec![0; self.external_ram_size()];
 
-        // Log ROM loading
-        if let Ok(mut file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("logs/rom_load.log")
-        {
-            writeln!(
-                file,
-                "Loading ROM - Size: {} bytes, Title: {}",
-                self.cartridge_rom.len(),
-                String::from_utf8_lossy(&self.cartridge_rom.get(0x134..0x144).unwrap_or(&[]))
-            )?;
+        log::debug!(
+            "Loading ROM - Size: {} bytes, Title: {}",
+            self.cartridge_rom.len(),
+            self.rom_title()
+        );
+
+        // Start from the boot ROM if one is loaded, otherwise from the post-boot state
+        self.init_system_state();
+
+        Ok(())
+    }
+    /// Build the bank controller described by the cartridge header (0x147)
+    fn create_cartridge_mbc(&self) -> Option<Box<dyn MBCController>> {
+        let cartridge_type = self.cartridge_rom.get(0x147).copied().unwrap_or(0x00);
+        let mbc = mbc::create_mbc(cartridge_type);
+        if mbc.is_none() && cartridge_type != 0x00 {
+            log::warn!(
+                "Unsupported cartridge type 0x{:02X}, running as ROM only",
+                cartridge_type
+            );
         }
+        mbc
+    }
 
-        // Initialize basic system state after ROM load
-        self.init_system_state()?;
+    /// Cartridge RAM size from header byte 0x149; MBC2 always has 512 built-in nibbles
+    fn external_ram_size(&self) -> usize {
+        if self.mbc_state.mbc_type == MBCType::MBC2 {
+            return 0x200;
+        }
+        mbc::types::get_ram_size_bytes(self.cartridge_rom.get(0x149).copied().unwrap_or(0))
+    }
 
+    /// Back battery-buffered cartridge RAM with a `.sav` file, loading it if it exists
+    ///
+    /// Carts with an RTC keep the clock state in a footer after the RAM contents.
+    pub fn attach_save_file(&mut self, path: PathBuf) -> Result<()> {
+        let has_rtc = self.mbc_state.rtc_enabled;
+        if !self.mbc_state.battery_backed || (self.external_ram.is_empty() && !has_rtc) {
+            log::info!("Cartridge has no battery-backed RAM, saving disabled");
+            return Ok(());
+        }
+
+        let save = SaveFile::new(path);
+        if let Some(data) = save.load()? {
+            let ram_len = self.external_ram.len().min(data.len());
+            self.external_ram[..ram_len].copy_from_slice(&data[..ram_len]);
+
+            let footer = &data[ram_len..];
+            let footer_loaded = match self.mbc.as_mut() {
+                Some(mbc) if has_rtc => mbc.load_footer(footer),
+                _ => footer.is_empty(),
+            };
+            if data.len() < self.external_ram.len() || !footer_loaded {
+                log::warn!(
+                    "Save file {} has unexpected size {} bytes",
+                    save.path().display(),
+                    data.len()
+                );
+            }
+            log::info!("Loaded cartridge RAM from {}", save.path().display());
+        }
+        self.save_file = Some(save);
         Ok(())
     }
-    /// Initialize basic system state for proper Game Boy operation
-    fn init_system_state(&mut self) -> Result<()> {
-        // Initialize LCD registers to enable display
-        self.lcd_registers.lcdc = 0x91; // Enable LCD, background, and use 8x8 sprites
-        self.lcd_registers.stat = 0x02; // Start in OAM scan mode
-        self.lcd_registers.scy = 0; // Scroll Y
-        self.lcd_registers.scx = 0; // Scroll X        self.lcd_registers.bgp = 0xE4; // Background palette (11100100)
-        self.lcd_registers.obp0 = 0xFF; // Sprite palette 0
-        self.lcd_registers.obp1 = 0xFF; // Sprite palette 1
-        self.lcd_registers.wy = 0; // Window Y
-        self.lcd_registers.wx = 0; // Window X
 
-        // Initialize LY and LYC
-        self.ly = 0;
-        self.lyc = 0;
+    /// Write battery-backed RAM (and RTC footer) to disk
+    pub fn flush_save(&mut self) -> Result<()> {
+        let save = match self.save_file.as_mut() {
+            Some(save) => save,
+            None => return Ok(()),
+        };
+
+        // The RTC keeps running while RAM is untouched, so clocked carts always save
+        let footer = self.mbc.as_ref().and_then(|mbc| mbc.save_footer());
+        if !save.is_dirty() && footer.is_none() {
+            return Ok(());
+        }
+
+        let mut data = self.external_ram.clone();
+        data.extend(footer.unwrap_or_default());
+        save.write(&data)
+    }
+
+    /// Flush battery-backed RAM once it has been dirty for a while
+    pub fn flush_save_if_due(&mut self) -> Result<()> {
+        match &self.save_file {
+            Some(save) if save.is_due() => self.flush_save(),
+            _ => Ok(()),
+        }
+    }
 
-        // Clear interrupt flags
-        self.interrupt_flags = 0;
+    /// Advance the timer, serial port and cartridge hardware such as the
+    /// MBC3 real-time clock
+    pub fn step(&mut self, cycles: u32) {
+        if self.timer.step(cycles) {
+            self.request_interrupt(Interrupt::Timer);
+        }
+
+        if self.serial.step(cycles) {
+            self.request_interrupt(Interrupt::Serial);
+        }
+
+        if let Some(mbc) = self.mbc.as_mut() {
+            mbc.step(cycles);
+        }
+
+        for _ in 0..cycles / 4 {
+            if let Some((source, offset)) = self.dma.tick() {
+                self.object_attribute_memory[offset] = self.read_dma_source(source);
+            }
+        }
+    }
+
+    /// Plug a link cable backend into the serial port, returning the old one
+    pub fn set_serial_link(&mut self, link: Box<dyn SerialLink>) -> Box<dyn SerialLink> {
+        self.serial.set_link(link)
+    }
+
+    /// Read a DMA source byte, bypassing the bus lock the transfer itself holds
+    fn read_dma_source(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => self.read_rom(address),
+            0x8000..=0x9FFF => self.video_ram[address as usize - 0x8000],
+            0xA000..=0xBFFF => self.read_external_ram(address),
+            _ => self.work_ram[(address as usize - 0xC000) & 0x1FFF],
+        }
+    }
+
+    /// Set up the I/O state execution starts from: power-on values when a
+    /// boot ROM runs first, otherwise what the model's boot ROM leaves behind
+    fn init_system_state(&mut self) {
+        self.boot_rom_mapped = self.boot_rom.is_some();
+        self.lcd_registers = LCDRegisters::new();
+        self.timer = Timer::new();
+        self.serial.reset();
+        self.dma = Dma::new();
         self.interrupt_enable = 0;
+        self.io_registers[0x00] = 0x00; // P1 reads 0xCF with no key held
 
-        // Initialize some I/O registers to reasonable defaults
-        self.io_registers[0x00] = 0xFF; // Joypad register
+        if self.boot_rom_mapped {
+            // The boot ROM turns on the LCD, sound and palettes itself
+            self.lcd_registers.lcdc = 0x00;
+            self.lcd_registers.bgp = 0x00;
+            self.lcd_registers.obp0 = 0x00;
+            self.lcd_registers.obp1 = 0x00;
+            self.interrupt_flags = 0x00;
+            self.io_registers[0x10..=0x26].fill(0);
+        } else {
+            // Values as documented in Pan Docs, "Power Up Sequence"
+            self.lcd_registers.lcdc = 0x91; // LCD, BG on, tile data at 0x8000
+            self.lcd_registers.stat = 0x05; // Reads 0x85
+            self.lcd_registers.bgp = 0xFC;
+            self.lcd_registers.obp0 = 0xFF;
+            self.lcd_registers.obp1 = 0xFF;
+            self.interrupt_flags = 0x01; // Reads 0xE1, V-Blank left pending
+            self.io_registers[0x10..=0x26].copy_from_slice(&POST_BOOT_SOUND);
 
-        // Keep VRAM clear - let the game initialize it
-        self.video_ram.fill(0);
+            // DIV keeps counting through the boot ROM, whose run time
+            // differs between models; the CGB value is for a CGB game
+            let (div, dma) = match self.model {
+                Model::Dmg | Model::Mgb => (0xABCC, 0xFF),
+                Model::Cgb => (0x1EA0, 0x00),
+            };
+            self.timer.set_counter(div);
+            self.lcd_registers.dma = dma;
 
-        // Log system initialization
-        if let Ok(mut file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("logs/system_init.log")
-        {
-            writeln!(
-                file,
-                "System state initialized for game - LCDC: 0x{:02X}, BGP: 0x{:02X}",
-                self.lcd_registers.lcdc, self.lcd_registers.bgp
-            )?;
+            // The CGB boot ROM leaves the internal clock selected, so SC reads 0x7F
+            if self.model == Model::Cgb {
+                self.serial.sc = 0x01;
+            }
         }
 
-        Ok(())
+        // Keep VRAM clear - let the game initialize it
+        self.video_ram.fill(0);
+
+        log::debug!(
+            "System state initialized for {:?} - boot ROM: {}, LCDC: 0x{:02X}",
+            self.model,
+            self.boot_rom_mapped,
+            self.lcd_registers.lcdc
+        );
     }
 
     pub fn reset(&mut self) {
@@ -352,109 +685,73 @@ impl MMU {
         self.video_ram.fill(0);
         self.object_attribute_memory.fill(0);
         self.io_registers.fill(0);
-        self.interrupt_enable = 0;
-        self.interrupt_flags = 0;
-        self.lcd_registers = LCDRegisters::new();
-        self.ly = 0;
-        self.lyc = 0;
+
+        // Bank controller registers return to their power-on state; the
+        // battery-backed RTC keeps running, as on hardware
+        if let Some(mbc) = self.mbc.as_mut() {
+            mbc.reset_banking();
+        }
+
+        // Remaps the boot ROM, if any
+        self.init_system_state();
+    }
+    /// Called by the PPU whenever its mode or LY changes
+    pub fn update_lcd_status(&mut self, mode: u8, ly: u8) {
+        if self.lcd_registers.update_status(mode, ly) {
+            self.request_interrupt(Interrupt::LcdStat);
+        }
+    }
+
+    /// Re-evaluate the STAT interrupt line after a STAT or LYC write
+    fn check_stat_interrupt(&mut self) {
+        if self.lcd_enabled() && self.lcd_registers.check_stat_interrupt() {
+            self.request_interrupt(Interrupt::LcdStat);
+        }
+    }
+
+    /// Set the interrupt's bit in IF (0xFF0F)
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt_flags |= 1 << interrupt.to_bit();
     }
+
+    /// Latch the current button state; the game reads it through 0xFF00
     pub fn update_joypad_state(&mut self, joypad: &dyn Joypad) {
-        let mut value = 0xFF;
-
-        // Select button state if requested
-        if (self.io_registers[0x00] & 0x20) == 0 {
-            value &= !(((joypad.is_start_pressed() as u8) << 3)
-                | ((joypad.is_select_pressed() as u8) << 2)
-                | ((joypad.is_b_pressed() as u8) << 1)
-                | (joypad.is_a_pressed() as u8));
-        }
-
-        // Select directional state if requested
-        if (self.io_registers[0x00] & 0x10) == 0 {
-            value &= !(((joypad.is_down_pressed() as u8) << 3)
-                | ((joypad.is_up_pressed() as u8) << 2)
-                | ((joypad.is_left_pressed() as u8) << 1)
-                | (joypad.is_right_pressed() as u8));
-        }
-
-        self.io_registers[0x00] = (self.io_registers[0x00] & 0xF0) | (value & 0x0F);
-    }
-
-    /// Display the boot animation with Nintendo logo
-    pub fn show_boot_sequence(&mut self) -> Result<()> {
-        // Copy Nintendo logo data to VRAM tile pattern table
-        // Nintendo logo starts at tile $19 (25 decimal)
-        let logo_start_tile = 25;
-        let vram_start = logo_start_tile * 16; // Each tile is 16 bytes
-        for (i, &byte) in NINTENDO_LOGO.iter().enumerate() {
-            self.video_ram[vram_start + i] = byte;
-        }
-
-        // Set up background tile map
-        let bg_map_start = 0x1800; // Background tile map at 0x1800-0x1BFF
-        let logo_width = 12; // Logo is 12 tiles wide
-        let logo_x = 4; // Position logo 4 tiles from left
-        let logo_y = 4; // Position logo 4 tiles from top
-
-        // Place logo tiles in background map
-        for i in 0..logo_width {
-            let map_pos = bg_map_start + (logo_y * 32 + logo_x + i) as usize;
-            self.video_ram[map_pos] = (logo_start_tile + i) as u8;
-        }
-
-        // Set up display parameters
-        self.lcd_registers.scy = 144; // Start from bottom
-        self.lcd_registers.scx = 0; // Center horizontally
-        self.lcd_registers.bgp = 0xE4; // Set palette (11100100)
-        self.lcd_registers.stat = 0; // Clear LCD status
-
-        // Enable LCD and background
-        self.lcd_registers.lcdc = 0x91; // 10010001
-                                        // Bit 7 = 1: LCD on
-                                        // Bit 6 = 0: Window tile map at 0x9800
-                                        // Bit 5 = 0: Window disabled
-                                        // Bit 4 = 1: BG tile data at 0x8000
-                                        // Bit 3 = 0: BG tile map at 0x9800
-                                        // Bit 2 = 0: 8x8 sprites
-                                        // Bit 1 = 0: Sprites disabled
-                                        // Bit 0 = 1: Background enabled
+        let old = self.joypad_register();
 
-        Ok(())
+        let buttons = ((joypad.is_start_pressed() as u8) << 3)
+            | ((joypad.is_select_pressed() as u8) << 2)
+            | ((joypad.is_b_pressed() as u8) << 1)
+            | (joypad.is_a_pressed() as u8);
+        let directions = ((joypad.is_down_pressed() as u8) << 3)
+            | ((joypad.is_up_pressed() as u8) << 2)
+            | ((joypad.is_left_pressed() as u8) << 1)
+            | (joypad.is_right_pressed() as u8);
+        self.joypad_keys = !(buttons | (directions << 4));
+
+        self.check_joypad_interrupt(old);
     }
 
-    /// Update boot animation scroll position
-    pub fn update_boot_animation(&mut self) -> bool {
-        if self.lcd_registers.scy > 72 {
-            // Target Y position is 72
-            self.lcd_registers.scy = self.lcd_registers.scy.saturating_sub(1);
-
-            // Log animation state
-            if let Ok(mut file) = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("logs/boot_animation.log")
-            {
-                let _ = writeln!(
-                    file,
-                    "Boot animation frame: SCY={}, LCDC={:08b}, STAT={:08b}, BGP={:08b}",
-                    self.lcd_registers.scy,
-                    self.lcd_registers.lcdc,
-                    self.lcd_registers.stat,
-                    self.lcd_registers.bgp
-                );
-            }
+    /// P1/JOYP value: select bits as last written, key lines of the selected groups (0 = pressed)
+    fn joypad_register(&self) -> u8 {
+        let select = self.io_registers[0x00] & 0x30;
+        let mut lines = 0x0F;
 
-            true // Animation still in progress
-        } else {
-            // Log animation completion
-            if let Ok(mut file) = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("logs/boot_animation.log")
-            {
-                let _ = writeln!(file, "Boot animation completed");
-            }
-            false // Animation complete
+        // P15 low selects the action buttons
+        if select & 0x20 == 0 {
+            lines &= self.joypad_keys & 0x0F;
+        }
+        // P14 low selects the direction keys
+        if select & 0x10 == 0 {
+            lines &= self.joypad_keys >> 4;
+        }
+
+        0xC0 | select | lines
+    }
+
+    /// Request the joypad interrupt when any key line goes from high to low
+    fn check_joypad_interrupt(&mut self, old: u8) {
+        if old & !self.joypad_register() & 0x0F != 0 {
+            self.request_interrupt(Interrupt::Joypad);
         }
     }
 }
@@ -464,3 +761,323 @@ impl Default for MMU {
         Self::new()
     }
 }
+
+// The cartridge ROM, boot ROM, model and save file come from the machine's
+// setup rather than its state; held buttons stay those of the live joypad
+impl SaveState for MMU {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.boot_rom_mapped);
+        w.bytes(&self.work_ram);
+        w.bytes(&self.high_ram);
+        w.bytes(&self.video_ram);
+        w.bytes(&self.object_attribute_memory);
+        w.bytes(&self.io_registers);
+        w.u8(self.interrupt_enable);
+        w.u8(self.interrupt_flags);
+        self.lcd_registers.save_state(w);
+        self.serial.save_state(w);
+        self.timer.save_state(w);
+        self.dma.save_state(w);
+
+        w.bool(self.mbc.is_some());
+        if let Some(mbc) = &self.mbc {
+            mbc.save_state(w);
+        }
+        w.bytes(&self.external_ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.boot_rom_mapped = r.bool()? && self.boot_rom.is_some();
+        r.bytes_into(&mut self.work_ram)?;
+        r.bytes_into(&mut self.high_ram)?;
+        r.bytes_into(&mut self.video_ram)?;
+        r.bytes_into(&mut self.object_attribute_memory)?;
+        r.bytes_into(&mut self.io_registers)?;
+        self.interrupt_enable = r.u8()?;
+        self.interrupt_flags = r.u8()?;
+        self.lcd_registers.load_state(r)?;
+        self.serial.load_state(r)?;
+        self.timer.load_state(r)?;
+        self.dma.load_state(r)?;
+
+        match (r.bool()?, self.mbc.as_mut()) {
+            (true, Some(mbc)) => mbc.load_state(r)?,
+            (false, None) => {}
+            _ => return Err(StateError::Corrupt.into()),
+        }
+        r.bytes_into(&mut self.external_ram)?;
+        // Battery RAM now holds the state's contents
+        if let Some(save) = self.save_file.as_mut() {
+            save.mark_dirty();
+        }
+        Ok(())
+    }
+}
+
+impl Tick for MMU {
+    fn tick(&mut self, cycles: CyclesType) -> Result<()> {
+        self.step(cycles);
+        Ok(())
+    }
+}
+
+impl Bus for MMU {
+    fn read(&mut self, address: u16) -> Result<u8> {
+        self.read_byte(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> Result<()> {
+        self.write_byte(address, value)
+    }
+
+    fn peek(&self, address: u16) -> Result<u8> {
+        MMU::peek(self, address)
+    }
+
+    fn interrupt_enable(&self) -> u8 {
+        self.interrupt_enable
+    }
+
+    fn interrupt_flags(&self) -> u8 {
+        self.interrupt_flags
+    }
+
+    fn set_interrupt_flags(&mut self, value: u8) {
+        self.interrupt_flags = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a ROM whose every bank starts with its own bank number
+    fn banked_rom(cartridge_type: u8, banks: usize) -> Vec<u8> {
+        let mut rom = vec![0u8; banks * 0x4000];
+        for bank in 0..banks {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom[0x147] = cartridge_type;
+        rom
+    }
+
+    #[test]
+    fn test_mbc1_rom_bank_switching() {
+        let mut mmu = MMU::new();
+        mmu.load_rom(&banked_rom(0x01, 64)).unwrap();
+
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 1);
+        mmu.write_byte(0x2000, 0x05).unwrap();
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 5);
+
+        // Bank 0 is remapped to bank 1
+        mmu.write_byte(0x2000, 0x00).unwrap();
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 1);
+
+        // Upper bank bits come from 0x4000-0x5FFF
+        mmu.write_byte(0x2000, 0x02).unwrap();
+        mmu.write_byte(0x4000, 0x01).unwrap();
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 0x22);
+    }
+
+    #[test]
+    fn test_mbc5_bank_wraps_to_rom_size() {
+        let mut mmu = MMU::new();
+        mmu.load_rom(&banked_rom(0x19, 8)).unwrap();
+
+        mmu.write_byte(0x2000, 0x0A).unwrap();
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 2);
+        assert_eq!(mmu.read_byte(0x0000).unwrap(), 0);
+    }
+    #[test]
+    fn test_external_ram_gated_by_enable_latch() {
+        let mut rom = banked_rom(0x03, 4);
+        rom[0x149] = 0x03; // 32KB, 4 banks
+        let mut mmu = MMU::new();
+        mmu.load_rom(&rom).unwrap();
+
+        mmu.write_byte(0xA000, 0x42).unwrap();
+        assert_eq!(mmu.read_byte(0xA000).unwrap(), 0xFF);
+
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0xA000, 0x42).unwrap();
+        assert_eq!(mmu.read_byte(0xA000).unwrap(), 0x42);
+
+        // RAM banking only applies in MBC1 mode 1
+        mmu.write_byte(0x6000, 0x01).unwrap();
+        mmu.write_byte(0x4000, 0x02).unwrap();
+        assert_eq!(mmu.read_byte(0xA000).unwrap(), 0x00);
+        mmu.write_byte(0x4000, 0x00).unwrap();
+        assert_eq!(mmu.read_byte(0xA000).unwrap(), 0x42);
+
+        mmu.write_byte(0x0000, 0x00).unwrap();
+        assert_eq!(mmu.read_byte(0xA000).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn test_battery_ram_round_trips_through_save_file() {
+        let path = std::env::temp_dir().join(format!("gb_mmu_test_{}.sav", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut rom = banked_rom(0x1B, 4);
+        rom[0x149] = 0x02; // 8KB
+        let mut mmu = MMU::new();
+        mmu.load_rom(&rom).unwrap();
+        mmu.attach_save_file(path.clone()).unwrap();
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0xA123, 0x99).unwrap();
+        mmu.flush_save().unwrap();
+
+        let mut reloaded = MMU::new();
+        reloaded.load_rom(&rom).unwrap();
+        reloaded.attach_save_file(path.clone()).unwrap();
+        assert_eq!(reloaded.external_ram[0x123], 0x99);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_joypad_select_lines_and_interrupt() {
+        use crate::interface::input::joypad::{GameBoyKey, JoypadImpl};
+
+        let mut mmu = MMU::new();
+        let mut joypad = JoypadImpl::new();
+        joypad.press_key(GameBoyKey::Start);
+        joypad.press_key(GameBoyKey::Left);
+
+        // Nothing selected yet, so the press doesn't reach the lines
+        mmu.write_byte(0xFF00, 0x30).unwrap();
+        mmu.update_joypad_state(&joypad);
+        assert_eq!(mmu.read_byte(0xFF00).unwrap(), 0xFF);
+        assert_eq!(mmu.interrupt_flags, 0);
+
+        mmu.write_byte(0xFF00, 0x10).unwrap(); // Action buttons
+        assert_eq!(mmu.read_byte(0xFF00).unwrap(), 0xD7);
+        assert_eq!(mmu.interrupt_flags, 0x10);
+
+        mmu.write_byte(0xFF00, 0x20).unwrap(); // Directions
+        assert_eq!(mmu.read_byte(0xFF00).unwrap(), 0xED);
+    }
+
+    #[test]
+    fn test_stat_interrupt_fires_on_rising_edge() {
+        let mut mmu = MMU::new();
+        mmu.write_byte(0xFF40, 0x80).unwrap();
+        mmu.write_byte(0xFF45, 5).unwrap();
+        mmu.write_byte(0xFF41, 0x4F).unwrap(); // LYC and H-Blank sources, low bits ignored
+        mmu.update_lcd_status(2, 4);
+        mmu.interrupt_flags = 0;
+
+        mmu.update_lcd_status(2, 5);
+        assert_eq!(mmu.read_byte(0xFF41).unwrap(), 0xCE);
+        assert_eq!(mmu.read_byte(0xFF44).unwrap(), 5);
+        assert_eq!(mmu.interrupt_flags, 0x02);
+
+        // H-Blank while LYC=LY still holds the line high, so it's blocked
+        mmu.interrupt_flags = 0;
+        mmu.update_lcd_status(0, 5);
+        assert_eq!(mmu.interrupt_flags, 0);
+
+        mmu.update_lcd_status(2, 6);
+        mmu.update_lcd_status(0, 6);
+        assert_eq!(mmu.interrupt_flags, 0x02);
+
+        // Turning the LCD off resets LY and the mode
+        mmu.write_byte(0xFF40, 0x00).unwrap();
+        assert_eq!(mmu.read_byte(0xFF44).unwrap(), 0);
+        assert_eq!(mmu.read_byte(0xFF41).unwrap() & 0x03, 0);
+    }
+
+    #[test]
+    fn test_oam_dma_copies_and_locks_bus() {
+        let mut mmu = MMU::new();
+        for i in 0..0xA0 {
+            mmu.work_ram[0x1F00 + i] = i as u8;
+        }
+        mmu.write_byte(0xFF80, 0x42).unwrap();
+
+        // 0xFF pages read the work RAM mirror, so this copies from 0xDF00
+        mmu.write_byte(0xFF46, 0xFF).unwrap();
+        mmu.step(4 * 2);
+        assert_eq!(mmu.read_byte(0xFE00).unwrap(), 0xFF);
+        assert_eq!(mmu.read_byte(0xC000).unwrap(), 0xFF);
+        assert_eq!(mmu.read_byte(0xFF80).unwrap(), 0x42);
+        // Debuggers still see past the lock
+        assert_eq!(mmu.peek(0xDF05).unwrap(), 0x05);
+        assert_eq!(mmu.read_byte(0xFF46).unwrap(), 0xFF);
+
+        mmu.step(4 * 158);
+        assert_eq!(mmu.read_byte(0xFE00).unwrap(), 0xFF);
+        mmu.step(4);
+        assert_eq!(mmu.read_byte(0xFE00).unwrap(), 0x00);
+        assert_eq!(mmu.read_byte(0xFE9F).unwrap(), 0x9F);
+    }
+
+    #[test]
+    fn test_boot_rom_overlay_until_ff50() {
+        // The image size has to match the requested model
+        assert!(BootRom::new(vec![0xAA; 0x900], Model::Dmg).is_err());
+        assert!(BootRom::new(vec![0xAA; 0x100], Model::Cgb).is_err());
+
+        let mut mmu = MMU::new();
+        mmu.load_boot_rom(BootRom::new(vec![0xAA; 0x900], Model::Cgb).unwrap());
+        assert_eq!(mmu.model(), Model::Cgb);
+        mmu.load_rom(&banked_rom(0x00, 2)).unwrap();
+
+        // CGB boot ROMs leave the cartridge header visible
+        assert_eq!(mmu.read_byte(0x0000).unwrap(), 0xAA);
+        assert_eq!(mmu.read_byte(0x0147).unwrap(), 0x00);
+        assert_eq!(mmu.read_byte(0x0200).unwrap(), 0xAA);
+        assert_eq!(mmu.read_byte(0xFF40).unwrap(), 0x00);
+
+        mmu.write_byte(0xFF50, 0x11).unwrap();
+        assert_eq!(mmu.read_byte(0x0000).unwrap(), 0x00);
+        assert_eq!(mmu.read_byte(0x0200).unwrap(), 0x00);
+
+        // Reset maps it back in
+        mmu.reset();
+        assert!(mmu.boot_rom_mapped());
+        assert_eq!(mmu.read_byte(0x0000).unwrap(), 0xAA);
+    }
+
+    #[test]
+    fn test_post_boot_io_registers_per_model() {
+        for (model, div, sc, dma) in [
+            (Model::Dmg, 0xAB, 0x7E, 0xFF),
+            (Model::Mgb, 0xAB, 0x7E, 0xFF),
+            (Model::Cgb, 0x1E, 0x7F, 0x00),
+        ] {
+            let mut mmu = MMU::new();
+            mmu.set_model(model);
+            mmu.load_rom(&banked_rom(0x00, 2)).unwrap();
+
+            let read = |address| mmu.read_byte(address).unwrap();
+            assert_eq!(read(0xFF00), 0xCF, "{:?} P1", model);
+            assert_eq!(read(0xFF02), sc, "{:?} SC", model);
+            assert_eq!(read(0xFF04), div, "{:?} DIV", model);
+            assert_eq!(read(0xFF07), 0xF8, "{:?} TAC", model);
+            assert_eq!(read(0xFF0F), 0xE1, "{:?} IF", model);
+            assert_eq!(read(0xFF10), 0x80, "{:?} NR10", model);
+            assert_eq!(read(0xFF11), 0xBF, "{:?} NR11", model);
+            assert_eq!(read(0xFF12), 0xF3, "{:?} NR12", model);
+            assert_eq!(read(0xFF1A), 0x7F, "{:?} NR30", model);
+            assert_eq!(read(0xFF24), 0x77, "{:?} NR50", model);
+            assert_eq!(read(0xFF25), 0xF3, "{:?} NR51", model);
+            assert_eq!(read(0xFF26), 0xF1, "{:?} NR52", model);
+            assert_eq!(read(0xFF40), 0x91, "{:?} LCDC", model);
+            assert_eq!(read(0xFF41), 0x85, "{:?} STAT", model);
+            assert_eq!(read(0xFF46), dma, "{:?} DMA", model);
+            assert_eq!(read(0xFF47), 0xFC, "{:?} BGP", model);
+            assert_eq!(read(0xFF4D), 0xFF, "{:?} KEY1", model);
+            assert_eq!(read(0xFFFF), 0x00, "{:?} IE", model);
+        }
+
+        // Turning the APU off clears the sound registers
+        let mut mmu = MMU::new();
+        mmu.load_rom(&banked_rom(0x00, 2)).unwrap();
+        mmu.write_byte(0xFF26, 0x00).unwrap();
+        mmu.write_byte(0xFF24, 0x55).unwrap();
+        assert_eq!(mmu.read_byte(0xFF24).unwrap(), 0x00);
+        assert_eq!(mmu.read_byte(0xFF26).unwrap(), 0x70);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::error::Result;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long dirty cartridge RAM may stay unsaved before a periodic flush
+pub const SAVE_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// `.sav` file backing battery-buffered cartridge RAM
+#[derive(Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    dirty: bool,
+    last_flush: Instant,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            dirty: false,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Read the whole save file. Returns `None` if no save exists yet.
+    pub fn load(&self) -> Result<Option<Vec<u8>>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Whether dirty data has waited long enough for a periodic flush,
+    /// so a crash loses at most `SAVE_FLUSH_INTERVAL` of progress
+    pub fn is_due(&self) -> bool {
+        self.dirty && self.last_flush.elapsed() >= SAVE_FLUSH_INTERVAL
+    }
+
+    /// Replace the save file contents with `data`
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        write_atomic(&self.path, data)?;
+        self.dirty = false;
+        self.last_flush = Instant::now();
+        log::info!("Cartridge RAM saved to {}", self.path.display());
+        Ok(())
+    }
+}
+
+/// Location of the `.sav` file for a ROM: next to the ROM, or inside `save_dir` if set
+pub fn save_path(rom_path: &Path, save_dir: Option<&Path>) -> PathBuf {
+    let sav = rom_path.with_extension("sav");
+    match (save_dir, sav.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => sav,
+    }
+}
+
+/// Write through a temporary file and rename it over the target, so an
+/// interrupted write never leaves a truncated save behind
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_extension(format!("{}.tmp", extension));
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -1,7 +1,7 @@
 //! Background renderer, generates background layer pixels
 
 use crate::core::mmu::MMU;
-use crate::core::ppu::registers::{BGP, LCDC, SCX, SCY};
+use crate::core::ppu::registers::{LCDC, SCX, SCY};
 use crate::error::Result;
 use std::cell::RefCell;
 
@@ -20,7 +20,7 @@ impl BackgroundRenderer {
         }
     }
 
-    /// Generate background pixel color IDs (0~3) for one scanline, requires MMU to read VRAM/registers
+    /// Generate raw background color numbers (0~3, before BGP) for one scanline
     pub fn render_line(&self, line: u8, mmu: &MMU) -> Result<Vec<u8>> {
         let mut result = vec![0u8; 160]; // Default to white (0)
 
@@ -34,7 +34,6 @@ impl BackgroundRenderer {
 
         let scx = mmu.read_byte(SCX)?;
         let scy = mmu.read_byte(SCY)?;
-        let bgp = mmu.read_byte(BGP)?;
         let vram = mmu.vram(); // Debug info: print register values for first line
                                // Disabled for performance
                                /*
@@ -65,8 +64,7 @@ impl BackgroundRenderer {
                                */ // Remove duplicate background enable check, as it's already checked above
 
         // Determine tile map start address
-        let bg_tile_map = if (lcdc & 0x08) != 0 { 0x1C00 } else { 0x1800 }; // Determine tile data section (in vram array indices)
-        let tile_data_base = if (lcdc & 0x10) != 0 { 0x0000 } else { 0x0800 };
+        let bg_tile_map = if (lcdc & 0x08) != 0 { 0x1C00 } else { 0x1800 };
 
         for x in 0..160u8 {
             let x_pos = (x as u16 + scx as u16) & 0xFF;
@@ -76,39 +74,9 @@ impl BackgroundRenderer {
             let tile_map_index = bg_tile_map + tile_y * 32 + tile_x;
             let tile_index = vram[tile_map_index];
 
-            // Calculate tile data address in vram array
-            let tile_addr = if (lcdc & 0x10) != 0 {
-                tile_data_base + (tile_index as usize * 16)
-            } else {
-                tile_data_base + ((tile_index as i8 as i16 + 128) as usize * 16)
-            };
-            let py = (y_pos % 8) as usize;
-            let px = (x_pos % 8) as usize;
-            let tile_data_addr = tile_addr + py * 2;
-            let byte1 = vram.get(tile_data_addr).copied().unwrap_or(0);
-            let byte2 = vram.get(tile_data_addr + 1).copied().unwrap_or(0); // Removed tile reading logs for performance
-                                                                            /*
-                                                                            if line == 0 && x == 0 {
-                                                                                if let Ok(mut file) = std::fs::OpenOptions::new()
-                                                                                    .create(true)
-                                                                                    .append(true)
-                                                                                    .open("logs/vram_write.log")
-                                                                                {
-                                                                                    let _ = writeln!(
-                                                                                        file,
-                                                                                        "Reading Tile: map_index=0x{:04X}, tile_index={}, tile_data_addr=0x{:04X}, Data=[{:02X},{:02X}]",
-                                                                                        tile_map_index, tile_index, tile_data_addr, byte1, byte2
-                                                                                    );
-                                                                                }
-                                                                            }
-                                                                            */
-
-            let color_bit = 7 - px;
-            let color_num = ((byte2 >> color_bit) & 1) << 1 | ((byte1 >> color_bit) & 1);
-
-            // Convert palette
-            let palette_color = (bgp >> (color_num * 2)) & 0x03;
-            result[x as usize] = palette_color;
+            // Palette is applied by the compositor, which needs raw color 0 for OBJ priority
+            let (px, py) = ((x_pos % 8) as usize, (y_pos % 8) as usize);
+            result[x as usize] = tile_pixel(vram, lcdc, tile_index, px, py);
         }
 
         Ok(result)
@@ -173,3 +141,19 @@ impl BackgroundRenderer {
         Ok(())
     }
 }
+
+/// Color number (0~3) of pixel (`px`, `py`) in a BG/window tile, using the
+/// addressing mode selected by LCDC bit 4
+pub(crate) fn tile_pixel(vram: &[u8], lcdc: u8, tile_index: u8, px: usize, py: usize) -> u8 {
+    let tile_addr = if (lcdc & 0x10) != 0 {
+        tile_index as usize * 16
+    } else {
+        // 0x8800 addressing: signed index relative to 0x9000
+        (0x1000 + (tile_index as i8 as i32) * 16) as usize
+    };
+
+    let byte1 = vram[tile_addr + py * 2];
+    let byte2 = vram[tile_addr + py * 2 + 1];
+    let color_bit = 7 - px;
+    ((byte2 >> color_bit) & 1) << 1 | ((byte1 >> color_bit) & 1)
+}
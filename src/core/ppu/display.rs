@@ -2,6 +2,8 @@
 
 use super::background::BackgroundRenderer;
 use crate::core::mmu::MMU;
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::StateError;
 
 #[derive(Debug)]
 pub struct Display {
@@ -55,10 +57,11 @@ impl Display {
         bg: &BackgroundRenderer,
         mmu: &MMU,
     ) -> crate::error::Result<()> {
+        let bgp = mmu.read_byte(crate::core::ppu::registers::BGP)?;
         for y in 0..144u8 {
             let line = bg.render_line(y, mmu)?;
             for x in 0..160u8 {
-                let color_id = line[x as usize];
+                let color_id = (bgp >> (line[x as usize] * 2)) & 0x03;
                 // Convert to grayscale colors according to Game Boy palette
                 let gray = match color_id {
                     0 => 0xFFFFFFFF,
@@ -102,3 +105,26 @@ impl Display {
             .collect()
     }
 }
+
+// The last picture, so it is on screen right after loading
+impl SaveState for Display {
+    fn save_state(&self, w: &mut StateWriter) {
+        let pixels: Vec<u8> = self
+            .framebuffer
+            .iter()
+            .flat_map(|pixel| pixel.to_le_bytes())
+            .collect();
+        w.bytes(&pixels);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> crate::error::Result<()> {
+        let pixels = r.bytes()?;
+        if pixels.len() != self.framebuffer.len() * 4 {
+            return Err(StateError::Corrupt.into());
+        }
+        for (pixel, bytes) in self.framebuffer.iter_mut().zip(pixels.chunks_exact(4)) {
+            *pixel = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        Ok(())
+    }
+}
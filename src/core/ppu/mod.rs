@@ -15,11 +15,12 @@ pub(crate) use display::*;
 pub(crate) use sprite::*;
 pub(crate) use window::*;
 
+use crate::core::cpu::interrupts::Interrupt;
 use crate::core::mmu::MMU;
-use crate::error::Error;
+use crate::core::ppu::registers::{BGP, LCDC, OBP0, OBP1};
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::{Error, StateError};
 use crate::interface::video::VideoInterface;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 const SCREEN_WIDTH: usize = 160;
 const SCREEN_HEIGHT: usize = 144;
@@ -32,27 +33,27 @@ pub struct PPU {
     pub window: WindowRenderer,
     pub sprites: SpriteRenderer,
     pub display: Display,
-    mmu: Rc<RefCell<MMU>>,
     video: Box<dyn VideoInterface>,
 
     // PPU state
     mode_clock: u32,
     current_line: u8,
     current_mode: u8,
+    frame_ready: bool, // Entered V-Blank since the last `take_frame_ready`
 }
 
 impl PPU {
-    pub fn new(mmu: Rc<RefCell<MMU>>, video: Box<dyn VideoInterface>) -> Self {
+    pub fn new(video: Box<dyn VideoInterface>) -> Self {
         Self {
             background: BackgroundRenderer::new(),
             window: WindowRenderer::new(),
             sprites: SpriteRenderer::new(),
             display: Display::new(),
-            mmu,
             video,
             mode_clock: 0,
             current_line: 0,
             current_mode: 0,
+            frame_ready: false,
         }
     }
 
@@ -62,52 +63,63 @@ impl PPU {
         self.video.render()
     }
 
-    pub fn render_line(&mut self) -> Result<(), Error> {
+    /// Composite background, window and objects for the current line
+    pub fn render_line(&mut self, mmu: &MMU) -> Result<(), Error> {
         let current_line = self.current_line;
-        let bg_line = {
-            let mmu = self.mmu.borrow();
-            self.background.render_line(current_line, &mmu)?
+        let lcdc = mmu.read_byte(LCDC)?;
+        let bgp = mmu.read_byte(BGP)?;
+        let obp0 = mmu.read_byte(OBP0)?;
+        let obp1 = mmu.read_byte(OBP1)?;
+
+        // Raw color numbers, the window draws over the background
+        let mut bg_line = self.background.render_line(current_line, mmu)?;
+        self.window.render_line(current_line, mmu, &mut bg_line)?;
+
+        let obj_line = if lcdc & 0x02 != 0 {
+            self.sprites.render_line(current_line, mmu)?
+        } else {
+            vec![None; SCREEN_WIDTH]
         };
 
-        // Update display buffer
-        let base_index = current_line as usize * SCREEN_WIDTH;
-        for x in 0..SCREEN_WIDTH {
-            let color_id = bg_line[x];
-            self.display.get_frame_mut()[base_index + x] = match color_id {
-                0 => 0xFFFFFFFF, // White
-                1 => 0xFFAAAAAA, // Light gray
-                2 => 0xFF555555, // Dark gray
-                3 => 0xFF000000, // Black
-                _ => 0xFF000000, // Default to black
-            };
-        }
+        let shades: Vec<u8> = bg_line
+            .iter()
+            .zip(obj_line.iter())
+            .map(|(&bg, obj)| match obj {
+                // OBJ-behind-BG only yields to background colors 1-3
+                Some(obj) if !(obj.behind_bg && bg != 0) => {
+                    let palette = if obj.palette { obp1 } else { obp0 };
+                    (palette >> (obj.color * 2)) & 0x03
+                }
+                _ => (bgp >> (bg * 2)) & 0x03,
+            })
+            .collect();
 
+        self.display.update_line(current_line as usize, &shades);
         Ok(())
     }
 
     pub fn get_video_mut(&mut self) -> &mut dyn VideoInterface {
         self.video.as_mut()
     }
-    pub fn step(&mut self, cycles: u32) -> Result<(), Error> {
+    /// Advance by `cycles`, reading VRAM/OAM and raising interrupts through `mmu`
+    pub fn step(&mut self, mmu: &mut MMU, cycles: u32) -> Result<(), Error> {
         // Check if LCD is enabled
-        let lcd_enabled = {
-            let mmu = self.mmu.borrow();
-            let lcdc = mmu.read_byte(0xFF40).unwrap_or(0);
-            (lcdc & 0x80) != 0
-        };
+        let lcd_enabled = (mmu.read_byte(0xFF40).unwrap_or(0) & 0x80) != 0;
 
         if !lcd_enabled {
             // When LCD is disabled
             self.display.clear();
+            self.window.reset_frame();
             self.current_mode = 0;
             self.current_line = 0;
             self.mode_clock = 0;
-            self.update_lcd_status()?;
+            // The MMU already reset LY and the STAT mode when LCDC bit 7 was cleared
             return Ok(());
         }
 
         self.mode_clock += cycles;
         let old_mode = self.current_mode;
+        let old_line = self.current_line;
 
         match self.current_mode {
             0 => {
@@ -119,9 +131,7 @@ impl PPU {
                     if self.current_line == VBLANK_LINE {
                         // Enter V-Blank
                         self.current_mode = 1;
-                        let mut mmu = self.mmu.borrow_mut();
-                        mmu.interrupt_flags |= 1 << 0; // Set VBlank interrupt
-                        drop(mmu);
+                        mmu.request_interrupt(Interrupt::VBlank);
 
                         // Frame rendering complete, update display
                         self.vblank()?;
@@ -141,12 +151,16 @@ impl PPU {
                         // V-Blank ends, return to first line
                         self.current_mode = 2;
                         self.current_line = 0;
+                        self.window.reset_frame();
                     }
                 }
             }
             2 => {
                 // OAM scan (80 cycles)
                 if self.mode_clock >= 80 {
+                    // Select the objects on this line before pixel transfer starts
+                    self.sprites.update_sprites(mmu, self.current_line)?;
+
                     self.mode_clock = 0;
                     self.current_mode = 3;
                 }
@@ -159,47 +173,39 @@ impl PPU {
 
                     // Render current line
                     if self.current_line < SCREEN_HEIGHT as u8 {
-                        self.render_line()?;
+                        self.render_line(mmu)?;
                     }
                 }
             }
             _ => unreachable!(),
-        } // If mode changed, update LCD status
-        if old_mode != self.current_mode {
-            self.update_lcd_status()?;
-
-            // Log mode change - DISABLED FOR PERFORMANCE
-            /*
-            if let Ok(mut file) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("logs/debug_ppu.log")
-            {
-                writeln!(
-                    file,
-                    "LCD mode change: {} -> {}, line: {}, clock: {}",
-                    old_mode, self.current_mode, self.current_line, self.mode_clock
-                )
-                .ok();
-            }
-            */
+        } // If mode or line changed, update STAT and LY
+        if old_mode != self.current_mode || old_line != self.current_line {
+            // The MMU raises the STAT interrupt on a rising edge of its sources
+            mmu.update_lcd_status(self.current_mode, self.current_line);
         }
 
         Ok(())
     }
 
-    pub fn update(&mut self, cycles: u32) -> Result<(), Error> {
-        self.step(cycles)
+    pub fn update(&mut self, mmu: &mut MMU, cycles: u32) -> Result<(), Error> {
+        self.step(mmu, cycles)
     }
 
     pub fn reset(&mut self) -> Result<(), Error> {
         self.mode_clock = 0;
         self.current_line = 0;
         self.current_mode = 0;
+        self.frame_ready = false;
         self.display.clear();
+        self.window.reset_frame();
         Ok(())
     }
 
+    /// Whether a complete frame was drawn since the last call
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
     pub fn get_line(&self) -> u8 {
         self.current_line
     }
@@ -207,93 +213,135 @@ impl PPU {
     pub fn get_mode(&self) -> u8 {
         self.current_mode
     }
-    #[allow(dead_code)]
-    fn draw_line(&mut self, line: u8, mmu: &MMU) -> Result<(), Error> {
-        // Get background layer
-        let bg_line = self.background.render_line(line, mmu)?;
-
-        let base_index = line as usize * SCREEN_WIDTH;
-        for x in 0..SCREEN_WIDTH {
-            let color_id = bg_line[x];
-            self.display.get_frame_mut()[base_index + x] = match color_id {
-                0 => 0xFFFFFFFF, // White
-                1 => 0xFFAAAAAA, // Light gray
-                2 => 0xFF555555, // Dark gray
-                3 => 0xFF000000, // Black
-                _ => 0xFF000000, // Default to black
-            };
-        }
-        Ok(())
+    fn vblank(&mut self) -> Result<(), Error> {
+        // Every line has been composited by now, just present the frame
+        self.frame_ready = true;
+        self.display.render(&mut self.video)
     }
+}
 
-    fn render_current_frame(&mut self) -> Result<(), Error> {
-        // Clear entire screen
-        self.display.clear();
-
-        // Render each line
-        for line in 0..SCREEN_HEIGHT {
-            let line = line as u8;
-
-            // First get background line data
-            let bg_line = {
-                let mmu = self.mmu.borrow();
-                self.background.render_line(line, &mmu)?
-            };
-
-            // Then update display buffer
-            let base_index = line as usize * SCREEN_WIDTH;
-            for x in 0..SCREEN_WIDTH {
-                let color_id = bg_line[x];
-                self.display.get_frame_mut()[base_index + x] = match color_id {
-                    0 => 0xFFFFFFFF, // White
-                    1 => 0xFFAAAAAA, // Light gray
-                    2 => 0xFF555555, // Dark gray
-                    3 => 0xFF000000, // Black
-                    _ => 0xFF000000, // Default to black
-                };
-            }
-        }
-
-        Ok(())
+impl SaveState for PPU {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u32(self.mode_clock);
+        w.u8(self.current_line);
+        w.u8(self.current_mode);
+        self.window.save_state(w);
+        self.sprites.save_state(w);
+        self.display.save_state(w);
     }
 
-    fn vblank(&mut self) -> Result<(), Error> {
-        // Update screen during V-Blank
-        self.render_current_frame()?;
-        self.display.render(&mut self.video)
+    fn load_state(&mut self, r: &mut StateReader) -> crate::error::Result<()> {
+        self.mode_clock = r.u32()?;
+        self.current_line = r.u8()?;
+        self.current_mode = r.u8()?;
+        if self.current_line > MAX_LINE || self.current_mode > 3 {
+            return Err(StateError::Corrupt.into());
+        }
+        self.frame_ready = false;
+        self.window.load_state(r)?;
+        self.sprites.load_state(r)?;
+        // Mode 3 draws the current line with the objects the OAM scan picked
+        if self.current_mode == 3 && !self.sprites.covers_line(self.current_line) {
+            return Err(StateError::Corrupt.into());
+        }
+        self.display.load_state(r)
     }
+}
 
-    fn update_lcd_status(&mut self) -> Result<(), Error> {
-        let mut mmu = self.mmu.borrow_mut();
-        let mut stat = mmu.read_byte(0xFF41)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::any::Any;
 
-        // Clear current mode bits (0-1)
-        stat &= 0xFC;
-        // Set new mode
-        stat |= self.current_mode & 0x03;
+    #[derive(Debug)]
+    struct NullVideo;
 
-        // Update LYC=LY comparison flag (bit 2)
-        let lyc = mmu.read_byte(0xFF45)?;
-        if self.current_line == lyc {
-            stat |= 0x04;
-        } else {
-            stat &= !0x04;
+    impl VideoInterface for NullVideo {
+        fn update_frame(&mut self, _frame_buffer: Vec<u8>) {}
+        fn render(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+        fn resize(&mut self, _new_width: u32, _new_height: u32) -> Result<(), Error> {
+            Ok(())
         }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
 
-        // Write updated STAT
-        mmu.write_byte(0xFF41, stat)?;
+    #[test]
+    fn test_ppu_initialization() {
+        let mut ppu = PPU::new(Box::new(NullVideo));
+        assert_eq!(ppu.get_line(), 0);
+        assert_eq!(ppu.get_mode(), 0);
+        assert!(!ppu.take_frame_ready());
+    }
 
-        // Update LY register
-        mmu.write_byte(0xFF44, self.current_line)?;
+    #[test]
+    fn test_sprite_priority_over_background() {
+        let mut mmu = MMU::new();
+        mmu.write_byte(LCDC, 0x93).unwrap(); // LCD, BG, OBJ on, tiles at 0x8000
+        mmu.write_byte(BGP, 0xE4).unwrap();
+        mmu.write_byte(OBP0, 0xE4).unwrap();
+        mmu.write_byte(OBP1, 0x1B).unwrap(); // Inverted
+
+        // Tile 1: solid color 3, tile 2: solid color 1
+        for row in 0..8 {
+            mmu.video_ram[0x10 + row * 2] = 0xFF;
+            mmu.video_ram[0x10 + row * 2 + 1] = 0xFF;
+            mmu.video_ram[0x20 + row * 2] = 0xFF;
+        }
+        // Second background tile uses color 1
+        mmu.video_ram[0x1801] = 2;
+
+        // Object 0 over tiles 0-1 on OBP0, behind BG colors 1-3
+        mmu.object_attribute_memory[0..4].copy_from_slice(&[16, 12, 1, 0x80]);
+        // Object 1 on OBP1, half off the left edge
+        mmu.object_attribute_memory[4..8].copy_from_slice(&[16, 4, 1, 0x10]);
+
+        let mut ppu = PPU::new(Box::new(NullVideo));
+        ppu.sprites.update_sprites(&mmu, 0).unwrap();
+        ppu.render_line(&mmu).unwrap();
+
+        let frame = ppu.display.get_frame();
+        assert_eq!(frame[0], 0xFFFFFFFF); // OBP1 maps color 3 to white
+        assert_eq!(frame[4], 0xFF000000); // Over BG color 0
+        assert_eq!(frame[8], 0xFFAAAAAA); // Hidden behind BG color 1
+        assert_eq!(frame[16], 0xFFFFFFFF); // Background color 0
+    }
 
-        Ok(())
+    /// Mode 3 state on `line` holding one object at Y=16 (covering lines 0-7)
+    fn mode_3_state(line: u8, sprite_height: u8) -> Vec<u8> {
+        let ppu = PPU::new(Box::new(NullVideo));
+        let mut w = StateWriter::new();
+        w.u32(0);
+        w.u8(line);
+        w.u8(3);
+        ppu.window.save_state(&mut w);
+        w.u8(sprite_height);
+        w.u8(1);
+        for value in [16, 8, 0, 0, 0] {
+            w.u8(value);
+        }
+        ppu.display.save_state(&mut w);
+        w.into_inner()
     }
-}
 
-#[cfg(test)]
-mod tests {
     #[test]
-    fn test_ppu_initialization() {
-        // TODO: Implement tests
+    fn test_load_state_rejects_impossible_sprites() {
+        let mut ppu = PPU::new(Box::new(NullVideo));
+        let mut load = |line, sprite_height| {
+            let data = mode_3_state(line, sprite_height);
+            ppu.load_state(&mut StateReader::new(&data))
+        };
+
+        load(7, 8).unwrap();
+        load(15, 16).unwrap();
+        for (line, sprite_height) in [(7, 0), (7, 12), (8, 8), (16, 16)] {
+            assert!(matches!(
+                load(line, sprite_height),
+                Err(Error::State(StateError::Corrupt))
+            ));
+        }
     }
 }
@@ -1,7 +1,9 @@
 //! 精靈渲染器，產生精靈圖層像素
 
 use crate::core::mmu::MMU;
-use crate::error::Result;
+use crate::core::ppu::registers::LCDC;
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::{Result, StateError};
 
 #[derive(Debug, Clone, Copy)]
 pub struct SpriteFlags(u8);
@@ -34,19 +36,32 @@ pub struct Sprite {
     pub x: u8,
     pub tile: u8,
     pub flags: SpriteFlags,
+    pub index: u8, // OAM 中的編號，X 座標相同時編號小的優先
 }
 
 impl Sprite {
-    pub fn new(y: u8, x: u8, tile: u8, flags: u8) -> Self {
+    pub fn new(y: u8, x: u8, tile: u8, flags: u8, index: u8) -> Self {
         Self {
             y,
             x,
             tile,
             flags: SpriteFlags::new(flags),
+            index,
         }
     }
 }
 
+/// 精靈在某個像素上的輸出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpritePixel {
+    pub color: u8,       // 原始顏色編號 1~3 (0 為透明，不會輸出)
+    pub palette: bool,   // false = OBP0, true = OBP1
+    pub behind_bg: bool, // 背景顏色 1~3 會蓋過此像素
+}
+
+/// 每條掃描線最多顯示的精靈數
+const MAX_SPRITES_PER_LINE: usize = 10;
+
 #[derive(Debug, Default)]
 pub struct SpriteRenderer {
     sprites: Vec<Sprite>,
@@ -56,86 +71,128 @@ pub struct SpriteRenderer {
 impl SpriteRenderer {
     pub fn new() -> Self {
         Self {
-            sprites: Vec::with_capacity(40),
+            sprites: Vec::with_capacity(MAX_SPRITES_PER_LINE),
             sprite_height: 8,
         }
     }
 
-    pub fn update_sprites(&mut self, mmu: &MMU) -> Result<()> {
+    /// OAM 掃描 (mode 2)：依 OAM 順序選出覆蓋此掃描線的前 10 個精靈
+    pub fn update_sprites(&mut self, mmu: &MMU, line: u8) -> Result<()> {
         self.sprites.clear();
-        
-        // 從 OAM 中讀取精靈資料
-        for i in 0..40 {
-            let base = 0xFE00 + (i * 4);
-            let y = mmu.read_byte(base as u16)?;
-            let x = mmu.read_byte((base + 1) as u16)?;
-            let tile = mmu.read_byte((base + 2) as u16)?;
-            let flags = mmu.read_byte((base + 3) as u16)?;
-            
-            self.sprites.push(Sprite::new(y, x, tile, flags));
+        self.sprite_height = if mmu.read_byte(LCDC)? & 0x04 != 0 {
+            16
+        } else {
+            8
+        };
+
+        for (i, entry) in mmu.object_attribute_memory.chunks_exact(4).enumerate() {
+            // 精靈 Y 座標有 16 的偏移
+            let top = entry[0] as i16 - 16;
+            let row = line as i16 - top;
+            if row < 0 || row >= self.sprite_height as i16 {
+                continue;
+            }
+
+            // X 座標不在畫面內的精靈同樣佔用名額
+            self.sprites
+                .push(Sprite::new(entry[0], entry[1], entry[2], entry[3], i as u8));
+            if self.sprites.len() == MAX_SPRITES_PER_LINE {
+                break;
+            }
         }
-        
-        // 根據 X 座標排序精靈，優先度高的放在後面
-        self.sprites.sort_by(|a, b| a.x.cmp(&b.x));
+
+        // X 座標小的優先，相同時 OAM 編號小的優先
+        self.sprites.sort_by_key(|sprite| (sprite.x, sprite.index));
         Ok(())
     }
 
-    pub fn render_line(&self, line: u8, mmu: &MMU) -> Result<Vec<Option<u8>>> {
+    pub fn render_line(&self, line: u8, mmu: &MMU) -> Result<Vec<Option<SpritePixel>>> {
         let mut line_buffer = vec![None; 160];
-        
+        let vram = mmu.vram();
+
+        // 從優先度最低的開始繪製，讓高優先度精靈的不透明像素蓋在上面
         for sprite in self.sprites.iter().rev() {
-            let y_pos = sprite.y.wrapping_sub(16);
-            
-            // 檢查精靈是否在當前掃描線上
-            if line < y_pos || line >= y_pos.wrapping_add(self.sprite_height) {
-                continue;
+            let mut row = (line as i16 - (sprite.y as i16 - 16)) as u8;
+            if sprite.flags.y_flip() {
+                row = self.sprite_height - 1 - row;
             }
-            
-            let y_offset = line.wrapping_sub(y_pos);
-            let y = if sprite.flags.y_flip() { 
-                self.sprite_height.wrapping_sub(1).wrapping_sub(y_offset)
-            } else { 
-                y_offset 
+
+            // 8x16 模式忽略圖塊編號的最低位
+            let tile = if self.sprite_height == 16 {
+                (sprite.tile & 0xFE) + row / 8
+            } else {
+                sprite.tile
             };
-            
-            // 取得圖塊資料
-            let tile_addr = 0x8000 + (sprite.tile as u16 * 16) + ((y as u16) * 2);
-            let tile_low = mmu.read_byte(tile_addr)?;
-            let tile_high = mmu.read_byte(tile_addr + 1)?;
-            
-            let x_pos = sprite.x.wrapping_sub(8);
-            
-            // 遍歷精靈的每個像素
-            for bit_pos in 0..8u8 {
-                let screen_x = x_pos.wrapping_add(if sprite.flags.x_flip() {
-                    7 - bit_pos
-                } else {
-                    bit_pos
-                });
-                
-                // 檢查是否在螢幕範圍內
-                if screen_x >= 160 {
+
+            // 精靈固定使用 0x8000 定址
+            let tile_addr = tile as usize * 16 + (row % 8) as usize * 2;
+            let tile_low = vram[tile_addr];
+            let tile_high = vram[tile_addr + 1];
+
+            for px in 0..8u8 {
+                let screen_x = sprite.x as i16 - 8 + px as i16;
+                if !(0..160).contains(&screen_x) {
                     continue;
                 }
-                
-                let color_low = ((tile_low >> bit_pos) & 0x1) as u8;
-                let color_high = ((tile_high >> bit_pos) & 0x1) as u8;
-                let color_num = (color_high << 1) | color_low;
-                
+
+                let bit = if sprite.flags.x_flip() { px } else { 7 - px };
+                let color_num = (((tile_high >> bit) & 0x1) << 1) | ((tile_low >> bit) & 0x1);
+
                 // 顏色 0 是透明
                 if color_num == 0 {
                     continue;
                 }
-                
-                // 設置顏色
-                line_buffer[screen_x as usize] = Some(color_num);
+
+                line_buffer[screen_x as usize] = Some(SpritePixel {
+                    color: color_num,
+                    palette: sprite.flags.palette(),
+                    behind_bg: sprite.flags.priority(),
+                });
             }
         }
-        
+
         Ok(line_buffer)
     }
-    
-    pub fn set_sprite_height(&mut self, height: u8) {
-        self.sprite_height = height;
+
+    /// 選出的精靈是否都覆蓋 `line`，用來檢查讀入的存檔
+    pub fn covers_line(&self, line: u8) -> bool {
+        self.sprites.iter().all(|sprite| {
+            let row = line as i16 - (sprite.y as i16 - 16);
+            (0..self.sprite_height as i16).contains(&row)
+        })
+    }
+}
+
+// 保存 OAM 掃描選出的精靈，存檔時間點可能在掃描與繪製之間
+impl SaveState for SpriteRenderer {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.sprite_height);
+        w.u8(self.sprites.len() as u8);
+        for sprite in &self.sprites {
+            for value in [
+                sprite.y,
+                sprite.x,
+                sprite.tile,
+                sprite.flags.0,
+                sprite.index,
+            ] {
+                w.u8(value);
+            }
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.sprite_height = r.u8()?;
+        let count = r.u8()? as usize;
+        // 其他高度會讓 render_line 的列計算溢位
+        if !matches!(self.sprite_height, 8 | 16) || count > MAX_SPRITES_PER_LINE {
+            return Err(StateError::Corrupt.into());
+        }
+        self.sprites.clear();
+        for _ in 0..count {
+            let [y, x, tile, flags, index] = [r.u8()?, r.u8()?, r.u8()?, r.u8()?, r.u8()?];
+            self.sprites.push(Sprite::new(y, x, tile, flags, index));
+        }
+        Ok(())
     }
 }
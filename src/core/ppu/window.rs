@@ -1,32 +1,75 @@
 //! 視窗渲染器，產生視窗圖層像素
 
+use super::background::tile_pixel;
+use crate::core::mmu::MMU;
+use crate::core::ppu::registers::{LCDC, WX, WY};
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::Result;
+
 #[derive(Debug, Default)]
 pub struct WindowRenderer {
-    enabled: bool,
-    x: u8,
-    y: u8,
+    line_counter: u8,  // 視窗內部行計數器，只在視窗實際繪製的行遞增
+    y_triggered: bool, // 本幀中 LY 曾等於 WY
 }
 
 impl WindowRenderer {
     pub fn new() -> Self {
         Self {
-            enabled: false,
-            x: 0,
-            y: 0,
+            line_counter: 0,
+            y_triggered: false,
+        }
+    }
+
+    /// 以視窗像素覆蓋背景行 (原始顏色編號 0~3)
+    pub fn render_line(&mut self, line: u8, mmu: &MMU, bg_line: &mut [u8]) -> Result<()> {
+        let lcdc = mmu.read_byte(LCDC)?;
+        let wy = mmu.read_byte(WY)?;
+        let wx = mmu.read_byte(WX)?;
+
+        if line == wy {
+            self.y_triggered = true;
+        }
+
+        // DMG 上 LCDC bit 0 同時關閉背景與視窗；WX > 166 時視窗在畫面外
+        if lcdc & 0x20 == 0 || lcdc & 0x01 == 0 || !self.y_triggered || wx > 166 {
+            return Ok(());
+        }
+
+        let vram = mmu.vram();
+        let tile_map = if (lcdc & 0x40) != 0 { 0x1C00 } else { 0x1800 };
+        let window_y = self.line_counter as usize;
+        let start_x = wx as i16 - 7;
+
+        for (x, pixel) in bg_line.iter_mut().enumerate() {
+            let window_x = x as i16 - start_x;
+            if window_x < 0 {
+                continue;
+            }
+            let window_x = window_x as usize;
+            let tile_index = vram[tile_map + (window_y / 8) * 32 + window_x / 8];
+            *pixel = tile_pixel(vram, lcdc, tile_index, window_x % 8, window_y % 8);
         }
+
+        self.line_counter = self.line_counter.wrapping_add(1);
+        Ok(())
     }
 
-    pub fn render_line(&self, _line: u8) -> Vec<u8> {
-        // 回傳每個像素的顏色 ID（僅範例）
-        vec![0; 160]
+    /// 新的一幀開始時重設行計數器
+    pub fn reset_frame(&mut self) {
+        self.line_counter = 0;
+        self.y_triggered = false;
     }
+}
 
-    pub fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+impl SaveState for WindowRenderer {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.line_counter);
+        w.bool(self.y_triggered);
     }
 
-    pub fn set_position(&mut self, x: u8, y: u8) {
-        self.x = x;
-        self.y = y;
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.line_counter = r.u8()?;
+        self.y_triggered = r.bool()?;
+        Ok(())
     }
 }
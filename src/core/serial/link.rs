@@ -0,0 +1,259 @@
+// link.rs - 連接線後端
+// 以位元組為單位交換資料：主機 (內部時脈) 送出一個位元組時，
+// 從機同時移出自己的 SB，兩邊在 8 個位元後互換內容
+
+use crate::error::Result;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+
+/// 連接線另一端
+///
+/// 所有方法都不可阻塞：它們在 CPU 的匯流排存取與序列埠時脈中被呼叫。
+pub trait SerialLink: std::fmt::Debug + Send {
+    /// 本機以內部時脈開始傳送 `byte`
+    fn start(&mut self, byte: u8);
+
+    /// 對方針對最近一次 `start` 移入的位元組；尚未收到時回傳 `None`。
+    /// 序列埠在 8 個位元時間內反覆查詢，之後仍未收到則視為未接線
+    fn reply(&mut self) -> Option<u8>;
+
+    /// 本機以外部時脈等待中，若對方已開始傳送則送出 `outgoing`，
+    /// 並回傳收到的位元組；對方尚未傳送時回傳 `None`
+    fn poll(&mut self, outgoing: u8) -> Option<u8>;
+}
+
+/// 未接線：輸入線被上拉，讀到的位元全為 1，外部時脈永遠不會到來
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullLink;
+
+impl SerialLink for NullLink {
+    fn start(&mut self, _byte: u8) {}
+
+    fn reply(&mut self) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    fn poll(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// 記錄所有送出的位元組，行為同未接線；複製品共用同一份紀錄，
+/// 可在交給 MMU 之後讀取 (例如測試 ROM 的序列埠輸出)
+#[derive(Debug, Default, Clone)]
+pub struct CaptureLink {
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CaptureLink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 目前為止送出的位元組
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.lock().map(|b| b.clone()).unwrap_or_default()
+    }
+
+    /// 以文字形式讀取送出的內容
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes()).into_owned()
+    }
+
+    pub fn clear(&mut self) {
+        if let Ok(mut bytes) = self.bytes.lock() {
+            bytes.clear();
+        }
+    }
+}
+
+impl SerialLink for CaptureLink {
+    fn start(&mut self, byte: u8) {
+        if let Ok(mut bytes) = self.bytes.lock() {
+            bytes.push(byte);
+        }
+    }
+
+    fn reply(&mut self) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    fn poll(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// 封包種類：主機送出的資料與從機的回覆
+const MSG_DATA: u8 = 0x01;
+const MSG_REPLY: u8 = 0x02;
+
+/// 封包長度：種類、序號、資料
+const PACKET_LEN: usize = 3;
+
+/// 單次 `reply` / `poll` 最多讀取的封包數，對方持續送出資料時仍會返回
+const MAX_PACKETS_PER_CALL: usize = 16;
+
+/// 透過 TCP 連接另一個模擬器行程
+///
+/// 每次傳輸為兩個 3 位元組封包：主機送出 `[MSG_DATA, 序號, SB]`，
+/// 正在以外部時脈等待的從機以相同序號回覆 `[MSG_REPLY, 序號, SB]`。
+/// 主機憑序號丟棄逾時後才到的回覆，雙方不會因此錯開一個位元組。
+/// 通訊端為非阻塞模式，等待回覆不會拖慢模擬。
+#[derive(Debug)]
+pub struct TcpLink {
+    stream: TcpStream,
+    pending: Vec<u8>,           // 尚未湊滿一個封包的位元組
+    sequence: u8,               // 本機最近一次送出的 MSG_DATA 序號
+    queued: VecDeque<(u8, u8)>, // 等待回覆時收到的對方 MSG_DATA (序號, 資料)
+}
+
+impl TcpLink {
+    /// 在 `addr` 等待另一端連入 (會阻塞直到連線建立)
+    pub fn listen(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        Self::from_stream(stream)
+    }
+
+    /// 連線到正在 `listen` 的另一端
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        Self::from_stream(TcpStream::connect(addr)?)
+    }
+
+    fn from_stream(stream: TcpStream) -> Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            pending: Vec::with_capacity(PACKET_LEN),
+            sequence: 0,
+            queued: VecDeque::new(),
+        })
+    }
+
+    fn send(&mut self, kind: u8, sequence: u8, byte: u8) -> bool {
+        self.stream.write_all(&[kind, sequence, byte]).is_ok()
+    }
+
+    /// 讀取一個完整封包，尚未到齊時回傳 `None`。
+    /// 未湊滿的位元組保留到下次呼叫。
+    fn receive(&mut self) -> Option<(u8, u8, u8)> {
+        while self.pending.len() < PACKET_LEN {
+            let mut buf = [0u8; PACKET_LEN];
+            let want = PACKET_LEN - self.pending.len();
+            match self.stream.read(&mut buf[..want]) {
+                Ok(0) => return None, // 對方已斷線
+                Ok(n) => self.pending.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return None, // WouldBlock
+            }
+        }
+        let message = (self.pending[0], self.pending[1], self.pending[2]);
+        self.pending.clear();
+        Some(message)
+    }
+
+    /// 取出對方下一筆 MSG_DATA，略過逾時後才到的回覆 (已經沒有人在等)
+    fn next_data(&mut self) -> Option<(u8, u8)> {
+        for _ in 0..MAX_PACKETS_PER_CALL {
+            if let (MSG_DATA, sequence, value) = self.receive()? {
+                return Some((sequence, value));
+            }
+        }
+        None
+    }
+}
+
+impl SerialLink for TcpLink {
+    fn start(&mut self, byte: u8) {
+        self.sequence = self.sequence.wrapping_add(1);
+        // 送出失敗時不會有回覆，序列埠會當作未接線
+        self.send(MSG_DATA, self.sequence, byte);
+    }
+
+    fn reply(&mut self) -> Option<u8> {
+        for _ in 0..MAX_PACKETS_PER_CALL {
+            match self.receive()? {
+                (MSG_REPLY, sequence, value) if sequence == self.sequence => return Some(value),
+                // 雙方同時以內部時脈傳送：留給之後的 poll 回覆
+                (MSG_DATA, sequence, value) => self.queued.push_back((sequence, value)),
+                // 先前逾時的傳輸遲到的回覆
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn poll(&mut self, outgoing: u8) -> Option<u8> {
+        let (sequence, incoming) = match self.queued.pop_front() {
+            Some(data) => data,
+            None => self.next_data()?,
+        };
+        self.send(MSG_REPLY, sequence, outgoing);
+        Some(incoming)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected_pair() -> (TcpLink, TcpLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let master = TcpLink::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        (master, TcpLink::from_stream(stream).unwrap())
+    }
+
+    /// 反覆呼叫 `f` 直到傳回結果，等待封包經過本機迴路
+    fn wait_for<T>(mut f: impl FnMut() -> Option<T>) -> T {
+        for _ in 0..1000 {
+            if let Some(value) = f() {
+                return value;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("link never answered");
+    }
+
+    #[test]
+    fn test_late_reply_is_not_taken_for_the_next_byte() {
+        let (mut master, mut slave) = connected_pair();
+
+        // 從機太晚回覆，主機已放棄這個位元組
+        master.start(0x11);
+        assert_eq!(master.reply(), None);
+        master.start(0x22);
+
+        assert_eq!(wait_for(|| slave.poll(0xAA)), 0x11);
+        assert_eq!(wait_for(|| slave.poll(0xBB)), 0x22);
+        assert_eq!(wait_for(|| master.reply()), 0xBB);
+    }
+
+    #[test]
+    fn test_reply_returns_while_peer_floods_data() {
+        let (mut master, mut peer) = connected_pair();
+
+        master.start(0x22);
+        for byte in 0..100 {
+            peer.start(byte);
+        }
+        assert_eq!(wait_for(|| peer.poll(0xBB)), 0x22);
+
+        // 每次呼叫只讀有限的封包，對方的資料留給之後的 poll
+        let mut calls = 0;
+        assert_eq!(
+            wait_for(|| {
+                calls += 1;
+                master.reply()
+            }),
+            0xBB
+        );
+        assert!(calls > 100 / MAX_PACKETS_PER_CALL);
+        for byte in 0..100 {
+            assert_eq!(master.poll(0xFF), Some(byte));
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod link;
+mod port;
+pub use link::{CaptureLink, NullLink, SerialLink, TcpLink};
+pub use port::Serial;
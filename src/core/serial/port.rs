@@ -0,0 +1,248 @@
+use super::link::{NullLink, SerialLink};
+use crate::core::state::{SaveState, StateReader, StateWriter};
+use crate::error::{Error, HardwareError, Result};
+
+/// 內部時脈 8192 Hz，每個位元 512 個時脈週期
+const CYCLES_PER_BIT: u32 = 512;
+
+const SC_TRANSFER: u8 = 0x80; // 傳輸開始 / 進行中
+const SC_INTERNAL_CLOCK: u8 = 0x01; // 1 = 本機提供時脈 (主機)
+
+/// 序列埠 SB/SC (0xFF01-0xFF02)
+#[derive(Debug)]
+pub struct Serial {
+    pub sb: u8,
+    pub sc: u8,
+    link: Box<dyn SerialLink>,
+    outgoing: u8,         // 本次傳輸開始時的 SB
+    incoming: Option<u8>, // 對方移入的位元組，主機尚未收到回覆時為 None
+    bits_left: u8,
+    counter: u32, // 目前位元已經過的時脈週期
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            sb: 0,
+            sc: 0,
+            link: Box::new(NullLink),
+            outgoing: 0xFF,
+            incoming: None,
+            bits_left: 0,
+            counter: 0,
+        }
+    }
+
+    /// 更換連接線後端，回傳原本的後端
+    pub fn set_link(&mut self, link: Box<dyn SerialLink>) -> Box<dyn SerialLink> {
+        std::mem::replace(&mut self.link, link)
+    }
+
+    pub fn read_byte(&self, addr: u16) -> Result<u8> {
+        match addr {
+            0xFF01 => Ok(self.sb),
+            0xFF02 => Ok(self.sc | 0x7E),
+            _ => Err(Error::Hardware(HardwareError::Serial(format!(
+                "Invalid serial register address: {:#04X}",
+                addr
+            )))),
+        }
+    }
+
+    pub fn write_byte(&mut self, addr: u16, value: u8) -> Result<()> {
+        match addr {
+            0xFF01 => self.sb = value,
+            0xFF02 => {
+                self.sc = value & (SC_TRANSFER | SC_INTERNAL_CLOCK);
+                self.counter = 0;
+                self.bits_left = 0;
+                if self.sc == SC_TRANSFER | SC_INTERNAL_CLOCK {
+                    // 主機：對方的位元組在同樣的 8 個時脈內移入，由 step 收取
+                    self.link.start(self.sb);
+                    self.outgoing = self.sb;
+                    self.incoming = None;
+                    self.bits_left = 8;
+                }
+            }
+            _ => {
+                return Err(Error::Hardware(HardwareError::Serial(format!(
+                    "Invalid serial register address: {:#04X}",
+                    addr
+                ))))
+            }
+        }
+        Ok(())
+    }
+
+    /// 推進序列埠，回傳是否需要請求序列埠中斷 (IF bit 3)
+    pub fn step(&mut self, cycles: u32) -> bool {
+        if self.sc & SC_TRANSFER == 0 {
+            return false;
+        }
+
+        self.counter += cycles;
+        if self.sc & SC_INTERNAL_CLOCK == 0 {
+            // 從機：時脈由對方提供，每個位元時間向連接線查詢一次
+            if self.counter < CYCLES_PER_BIT {
+                return false;
+            }
+            self.counter = 0;
+            return match self.link.poll(self.sb) {
+                Some(byte) => {
+                    self.sb = byte;
+                    self.finish()
+                }
+                None => false,
+            };
+        }
+
+        while self.counter >= CYCLES_PER_BIT && self.bits_left > 0 {
+            self.counter -= CYCLES_PER_BIT;
+            if self.incoming.is_none() {
+                self.incoming = self.link.reply();
+            }
+            self.bits_left -= 1;
+
+            // SB 左移送出最高位，同時由右側移入對方的位元；
+            // 回覆較晚到達時，已移入的位元一併更正，到最後仍未收到則全為 1
+            let shifted = 8 - self.bits_left as u32;
+            let line = (self.outgoing as u16) << 8 | self.incoming.unwrap_or(0xFF) as u16;
+            self.sb = (line >> (8 - shifted)) as u8;
+        }
+        self.bits_left == 0 && self.finish()
+    }
+
+    /// 傳輸完成：清除 SC bit 7 並請求中斷
+    fn finish(&mut self) -> bool {
+        self.sc &= !SC_TRANSFER;
+        self.counter = 0;
+        true
+    }
+
+    /// 回到開機狀態，保留目前的連接線
+    pub fn reset(&mut self) {
+        let link = self.set_link(Box::new(NullLink));
+        *self = Self::new();
+        self.link = link;
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 連接線後端不屬於存檔狀態，載入後沿用目前的連接
+impl SaveState for Serial {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.sb);
+        w.u8(self.sc);
+        w.u8(self.outgoing);
+        w.bool(self.incoming.is_some());
+        w.u8(self.incoming.unwrap_or(0xFF));
+        w.u8(self.bits_left);
+        w.u32(self.counter);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.sb = r.u8()?;
+        self.sc = r.u8()?;
+        self.outgoing = r.u8()?;
+        let replied = r.bool()?;
+        let incoming = r.u8()?;
+        self.incoming = replied.then_some(incoming);
+        self.bits_left = r.u8()?;
+        self.counter = r.u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::link::CaptureLink;
+    use super::*;
+
+    #[test]
+    fn test_internal_clock_transfer_takes_eight_bits() {
+        let capture = CaptureLink::new();
+        let mut serial = Serial::new();
+        serial.set_link(Box::new(capture.clone()));
+
+        serial.write_byte(0xFF01, 0x41).unwrap();
+        serial.write_byte(0xFF02, 0x81).unwrap();
+        assert_eq!(capture.bytes(), vec![0x41]);
+
+        assert!(!serial.step(4 * CYCLES_PER_BIT));
+        assert_eq!(serial.sb, 0x1F); // 一半已移出，右側移入 1
+        assert!(!serial.step(4 * CYCLES_PER_BIT - 4));
+        assert!(serial.step(4));
+        assert_eq!(serial.sb, 0xFF);
+        assert_eq!(serial.read_byte(0xFF02).unwrap(), 0x7F);
+        assert!(!serial.step(CYCLES_PER_BIT));
+    }
+
+    /// 第 `delay` 次查詢才收到回覆的從機
+    #[derive(Debug)]
+    struct SlowSlave {
+        byte: u8,
+        delay: u32,
+    }
+
+    impl SerialLink for SlowSlave {
+        fn start(&mut self, _byte: u8) {}
+
+        fn reply(&mut self) -> Option<u8> {
+            self.delay = self.delay.checked_sub(1)?;
+            (self.delay == 0).then_some(self.byte)
+        }
+
+        fn poll(&mut self, _outgoing: u8) -> Option<u8> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_reply_is_collected_during_transfer() {
+        for (delay, received) in [(3, 0x5A), (8, 0x5A), (9, 0xFF)] {
+            let mut serial = Serial::new();
+            serial.set_link(Box::new(SlowSlave { byte: 0x5A, delay }));
+            serial.write_byte(0xFF01, 0x00).unwrap();
+            serial.write_byte(0xFF02, 0x81).unwrap();
+
+            assert!(!serial.step(4 * CYCLES_PER_BIT));
+            assert!(serial.step(4 * CYCLES_PER_BIT));
+            assert_eq!(serial.sb, received);
+        }
+    }
+
+    #[derive(Debug)]
+    struct Master(u8);
+
+    impl SerialLink for Master {
+        fn start(&mut self, _byte: u8) {}
+
+        fn reply(&mut self) -> Option<u8> {
+            Some(0xFF)
+        }
+
+        fn poll(&mut self, outgoing: u8) -> Option<u8> {
+            let incoming = self.0;
+            self.0 = outgoing;
+            Some(incoming)
+        }
+    }
+
+    #[test]
+    fn test_external_clock_waits_for_link() {
+        let mut serial = Serial::new();
+        serial.write_byte(0xFF01, 0x12).unwrap();
+        serial.write_byte(0xFF02, 0x80).unwrap();
+        assert!(!serial.step(8 * CYCLES_PER_BIT)); // 未接線，不會完成
+
+        serial.set_link(Box::new(Master(0x5A)));
+        assert!(serial.step(CYCLES_PER_BIT));
+        assert_eq!(serial.sb, 0x5A);
+        assert_eq!(serial.sc & SC_TRANSFER, 0);
+    }
+}
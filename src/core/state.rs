@@ -0,0 +1,281 @@
+// Save state serialization
+//
+// A state is a header identifying the format and the cartridge, a thumbnail,
+// then every component's state in a fixed order. All values are little-endian.
+
+use crate::error::{Result, StateError};
+use std::path::{Path, PathBuf};
+
+/// First bytes of every save state
+pub const STATE_MAGIC: [u8; 4] = *b"GBSS";
+/// Bumped whenever the layout of any component's state changes; states from
+/// other versions are rejected rather than misread
+pub const STATE_VERSION: u16 = 1;
+
+/// Thumbnail size, half the screen in each direction
+pub const THUMBNAIL_WIDTH: usize = 80;
+pub const THUMBNAIL_HEIGHT: usize = 72;
+
+/// A component whose state can be captured and restored
+pub trait SaveState {
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()>;
+}
+
+/// Builds a save state
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// A length-prefixed block of bytes
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+/// Reads a save state back; running out of data is reported as a corrupt state
+#[derive(Debug)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Whether every byte has been consumed
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The bytes not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(StateError::Corrupt.into());
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// A length-prefixed block of bytes
+    pub fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// A length-prefixed block that must exactly fill `target`
+    pub fn bytes_into(&mut self, target: &mut [u8]) -> Result<()> {
+        let bytes = self.bytes()?;
+        if bytes.len() != target.len() {
+            return Err(StateError::Corrupt.into());
+        }
+        target.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Identifies the format and the cartridge a state was taken from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateHeader {
+    pub version: u16,
+    /// Cartridge title from the ROM header (0x0134-0x0143)
+    pub title: String,
+    /// Global checksum from the ROM header (0x014E-0x014F)
+    pub checksum: u16,
+    /// `THUMBNAIL_WIDTH` x `THUMBNAIL_HEIGHT` pixels in framebuffer format
+    pub thumbnail: Vec<u32>,
+}
+
+impl StateHeader {
+    pub fn write(&self, w: &mut StateWriter) {
+        for byte in STATE_MAGIC {
+            w.u8(byte);
+        }
+        w.u16(self.version);
+        w.bytes(self.title.as_bytes());
+        w.u16(self.checksum);
+        let thumbnail: Vec<u8> = self
+            .thumbnail
+            .iter()
+            .flat_map(|pixel| pixel.to_le_bytes())
+            .collect();
+        w.bytes(&thumbnail);
+    }
+
+    /// Read the header, rejecting other formats and versions. Frontends can
+    /// use this to list slots without loading them.
+    pub fn read(r: &mut StateReader) -> Result<Self> {
+        if r.array::<4>().ok() != Some(STATE_MAGIC) {
+            return Err(StateError::NotAState.into());
+        }
+        let version = r.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion {
+                found: version,
+                expected: STATE_VERSION,
+            }
+            .into());
+        }
+
+        let title = String::from_utf8_lossy(r.bytes()?).into_owned();
+        let checksum = r.u16()?;
+        let thumbnail = r.bytes()?;
+        if thumbnail.len() != THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 4 {
+            return Err(StateError::Corrupt.into());
+        }
+        let thumbnail = thumbnail
+            .chunks_exact(4)
+            .map(|pixel| u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]))
+            .collect();
+        Ok(Self {
+            version,
+            title,
+            checksum,
+            thumbnail,
+        })
+    }
+
+    /// Parse just the header of a complete state
+    pub fn from_state(data: &[u8]) -> Result<Self> {
+        Self::read(&mut StateReader::new(data))
+    }
+}
+
+/// Downscale a 160x144 framebuffer to a thumbnail by averaging 2x2 blocks
+pub fn thumbnail(framebuffer: &[u32]) -> Vec<u32> {
+    let width = THUMBNAIL_WIDTH * 2;
+    let mut thumbnail = Vec::with_capacity(THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT);
+    for y in 0..THUMBNAIL_HEIGHT {
+        for x in 0..THUMBNAIL_WIDTH {
+            let block = [0, 1, width, width + 1].map(|offset| {
+                let pixel = framebuffer.get(y * 2 * width + x * 2 + offset);
+                pixel.copied().unwrap_or(0xFFFFFFFF)
+            });
+            // Average each 8-bit channel separately
+            let pixel = (0..4).fold(0u32, |pixel, channel| {
+                let shift = channel * 8;
+                let sum: u32 = block.iter().map(|p| (p >> shift) & 0xFF).sum();
+                pixel | ((sum / 4) << shift)
+            });
+            thumbnail.push(pixel);
+        }
+    }
+    thumbnail
+}
+
+/// File for save slot `slot` of a ROM: `<rom name>.ss<slot>` inside `dir`
+pub fn slot_path(rom_path: &Path, dir: &Path, slot: u8) -> PathBuf {
+    let name = rom_path.with_extension(format!("ss{}", slot));
+    dir.join(name.file_name().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_header_round_trip_and_version_check() {
+        let header = StateHeader {
+            version: STATE_VERSION,
+            title: "TETRIS".to_string(),
+            checksum: 0x16BF,
+            thumbnail: thumbnail(&[0xFF555555; 160 * 144]),
+        };
+        let mut w = StateWriter::new();
+        header.write(&mut w);
+        let mut data = w.into_inner();
+        assert_eq!(StateHeader::from_state(&data).unwrap(), header);
+        assert_eq!(header.thumbnail.len(), THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT);
+        assert_eq!(header.thumbnail[0], 0xFF555555);
+
+        assert!(matches!(
+            StateHeader::from_state(&data[..10]),
+            Err(Error::State(StateError::Corrupt))
+        ));
+        assert!(matches!(
+            StateHeader::from_state(b"GBSX"),
+            Err(Error::State(StateError::NotAState))
+        ));
+
+        // A thumbnail of the wrong size is rejected rather than handed to a frontend
+        let short = StateHeader {
+            thumbnail: vec![0; 10],
+            ..header.clone()
+        };
+        let mut w = StateWriter::new();
+        short.write(&mut w);
+        assert!(matches!(
+            StateHeader::from_state(&w.into_inner()),
+            Err(Error::State(StateError::Corrupt))
+        ));
+        data[4] = STATE_VERSION as u8 + 1;
+        assert!(matches!(
+            StateHeader::from_state(&data),
+            Err(Error::State(StateError::UnsupportedVersion { .. }))
+        ));
+    }
+}
@@ -1,31 +1,43 @@
+use crate::core::state::{SaveState, StateReader, StateWriter};
 use crate::error::{Error, Result};
 
 #[derive(Clone, Debug)]
 pub struct Timer {
-    pub div: u8,
+    counter: u16, // 內部 16 位元計數器，DIV 為其高 8 位
     pub tima: u8,
     pub tma: u8,
     pub tac: u8,
-    cycles: u32,
+    overflow_pending: bool, // TIMA 溢位後延遲 1 個 M-cycle 才載入 TMA
+    reloading: bool,        // 本 M-cycle 剛從 TMA 載入
 }
 
 impl Timer {
     pub fn new() -> Self {
         Self {
-            div: 0,
+            counter: 0,
             tima: 0,
             tma: 0,
             tac: 0,
-            cycles: 0,
+            overflow_pending: false,
+            reloading: false,
         }
     }
 
+    pub fn div(&self) -> u8 {
+        (self.counter >> 8) as u8
+    }
+
+    /// 設定內部計數器，用於還原開機程式結束時的狀態
+    pub fn set_counter(&mut self, counter: u16) {
+        self.counter = counter;
+    }
+
     pub fn read_byte(&self, addr: u16) -> Result<u8> {
         match addr {
-            0xFF04 => Ok(self.div),
+            0xFF04 => Ok(self.div()),
             0xFF05 => Ok(self.tima),
             0xFF06 => Ok(self.tma),
-            0xFF07 => Ok(self.tac),
+            0xFF07 => Ok(self.tac | 0xF8),
             _ => Err(Error::Hardware(crate::error::HardwareError::Timer(
                 format!("Invalid timer register address: {:#04X}", addr),
             ))),
@@ -34,10 +46,38 @@ impl Timer {
 
     pub fn write_byte(&mut self, addr: u16, value: u8) -> Result<()> {
         match addr {
-            0xFF04 => self.div = 0,
-            0xFF05 => self.tima = value,
-            0xFF06 => self.tma = value,
-            0xFF07 => self.tac = value & 0x07,
+            0xFF04 => {
+                // 重設計數器可能造成下降沿，使 TIMA 額外遞增
+                let old = self.timer_bit();
+                self.counter = 0;
+                if old {
+                    self.increment_tima();
+                }
+            }
+            0xFF05 => {
+                if self.overflow_pending {
+                    // 延遲期間寫入 TIMA 會取消重新載入與中斷
+                    self.overflow_pending = false;
+                    self.tima = value;
+                } else if !self.reloading {
+                    // 重新載入的那個 M-cycle 內寫入 TIMA 會被忽略
+                    self.tima = value;
+                }
+            }
+            0xFF06 => {
+                self.tma = value;
+                if self.reloading {
+                    self.tima = value;
+                }
+            }
+            0xFF07 => {
+                // 關閉計時器或切換頻率同樣可能造成下降沿
+                let old = self.timer_bit();
+                self.tac = value & 0x07;
+                if old && !self.timer_bit() {
+                    self.increment_tima();
+                }
+            }
             _ => {
                 return Err(Error::Hardware(crate::error::HardwareError::Timer(
                     format!("Invalid timer register address: {:#04X}", addr),
@@ -47,45 +87,115 @@ impl Timer {
         Ok(())
     }
 
-    pub fn step(&mut self, cycles: u32) -> Result<()> {
-        self.cycles += cycles;
-
-        // DIV 寄存器每 256 個時脈週期遞增
-        self.div = self.div.wrapping_add((self.cycles / 256) as u8);
-        self.cycles %= 256;
-
-        // 只有在 TAC 的最高位設定為 1 時才會更新 TIMA
-        if self.tac & 0x04 != 0 {
-            let ticks = match self.tac & 0x03 {
-                0 => 1024, // 4096 Hz
-                1 => 16,   // 262144 Hz
-                2 => 64,   // 65536 Hz
-                3 => 256,  // 16384 Hz
-                _ => unreachable!(),
-            };
-
-            while self.cycles >= ticks {
-                self.tima = self.tima.wrapping_add(1);
-                if self.tima == 0 {
-                    self.tima = self.tma;
-                }
-                self.cycles -= ticks;
+    /// 推進計時器，回傳是否需要請求計時器中斷 (IF bit 2)
+    pub fn step(&mut self, cycles: u32) -> bool {
+        let mut interrupt = false;
+
+        // 計時器以 M-cycle (4 個時脈週期) 為單位運作
+        for _ in 0..cycles / 4 {
+            self.reloading = false;
+            if self.overflow_pending {
+                self.overflow_pending = false;
+                self.tima = self.tma;
+                self.reloading = true;
+                interrupt = true;
+            }
+
+            // TIMA 在所選計數器位元與 TAC 啟用位的 AND 結果出現下降沿時遞增
+            let old = self.timer_bit();
+            self.counter = self.counter.wrapping_add(4);
+            if old && !self.timer_bit() {
+                self.increment_tima();
             }
         }
 
-        Ok(())
+        interrupt
+    }
+
+    /// TAC 選擇的計數器位元與啟用位的 AND 結果
+    fn timer_bit(&self) -> bool {
+        if self.tac & 0x04 == 0 {
+            return false;
+        }
+
+        let bit = match self.tac & 0x03 {
+            0 => 9, // 4096 Hz
+            1 => 3, // 262144 Hz
+            2 => 5, // 65536 Hz
+            3 => 7, // 16384 Hz
+            _ => unreachable!(),
+        };
+        self.counter & (1 << bit) != 0
+    }
+
+    fn increment_tima(&mut self) {
+        let (value, overflow) = self.tima.overflowing_add(1);
+        // 溢位後 TIMA 在延遲的 M-cycle 內讀取為 0
+        self.tima = value;
+        if overflow {
+            self.overflow_pending = true;
+        }
     }
 
     pub fn reset(&mut self) -> Result<()> {
-        self.div = 0;
-        self.tima = 0;
-        self.tma = 0;
-        self.tac = 0;
-        self.cycles = 0;
+        *self = Self::new();
         Ok(())
     }
 
-    pub fn update(&mut self, cycles: u32) -> Result<()> {
+    pub fn update(&mut self, cycles: u32) -> bool {
         self.step(cycles)
     }
 }
+
+impl SaveState for Timer {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.counter);
+        w.u8(self.tima);
+        w.u8(self.tma);
+        w.u8(self.tac);
+        w.bool(self.overflow_pending);
+        w.bool(self.reloading);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.counter = r.u16()?;
+        self.tima = r.u8()?;
+        self.tma = r.u8()?;
+        self.tac = r.u8()?;
+        self.overflow_pending = r.bool()?;
+        self.reloading = r.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tima_overflow_reloads_after_delay() {
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF06, 0xAB).unwrap();
+        timer.write_byte(0xFF05, 0xFF).unwrap();
+        timer.write_byte(0xFF07, 0x05).unwrap(); // 262144 Hz
+
+        assert!(!timer.step(16));
+        assert_eq!(timer.tima, 0x00);
+
+        assert!(timer.step(4));
+        assert_eq!(timer.tima, 0xAB);
+    }
+
+    #[test]
+    fn test_div_write_falling_edge_increments_tima() {
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF07, 0x04).unwrap(); // 4096 Hz, bit 9
+        timer.step(512);
+        assert_eq!(timer.div(), 0x02);
+        assert_eq!(timer.tima, 0);
+
+        timer.write_byte(0xFF04, 0x00).unwrap();
+        assert_eq!(timer.div(), 0);
+        assert_eq!(timer.tima, 1);
+    }
+}
@@ -17,6 +17,9 @@ pub enum HardwareError {
     #[error("Timer error: {0}")]
     Timer(String),
 
+    #[error("Serial error: {0}")]
+    Serial(String),
+
     #[error("PPU error: {0}")]
     PPU(String),
 
@@ -60,6 +63,10 @@ impl HardwareError {
         HardwareError::Timer(msg.into())
     }
 
+    pub fn serial(msg: impl Into<String>) -> Self {
+        HardwareError::Serial(msg.into())
+    }
+
     pub fn ppu(msg: impl Into<String>) -> Self {
         HardwareError::PPU(msg.into())
     }
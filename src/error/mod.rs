@@ -32,6 +32,24 @@ pub enum Error {
 
     #[error("Memory error: {0}")]
     Memory(String),
+
+    #[error("Save state error: {0}")]
+    State(#[from] StateError),
+}
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("not a save state")]
+    NotAState,
+
+    #[error("unsupported save state version {found} (this build reads version {expected})")]
+    UnsupportedVersion { found: u16, expected: u16 },
+
+    #[error("save state is for \"{title}\" (global checksum {checksum:04X}), not the loaded ROM")]
+    WrongRom { title: String, checksum: u16 },
+
+    #[error("save state is truncated or corrupt")]
+    Corrupt,
 }
 
 #[derive(Error, Debug)]
@@ -4,16 +4,23 @@ use cpal::{
 };
 use std::fmt;
 
-pub trait AudioInterface: fmt::Debug {
+pub trait AudioInterface: fmt::Debug + Send {
     fn push_sample(&mut self, sample: f32);
     fn start(&mut self);
     fn stop(&mut self);
+
+    /// Samples waiting to be played, if the backend can tell. Frontends pace
+    /// emulation to keep this topped up instead of following the wall clock.
+    fn queued_samples(&self) -> Option<usize> {
+        None
+    }
 }
 
 // Implement special Debug to handle cpal types
+// The cpal `Stream` is !Send, so it has to live on the thread that plays it
+// rather than in here; the emulator itself may run on a worker thread
 #[derive(Default)]
 pub struct CpalAudioOutput {
-    _stream: Option<Stream>,
     device_id: String,
     config: Option<StreamConfig>,
 }
@@ -22,7 +29,6 @@ impl fmt::Debug for CpalAudioOutput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("CpalAudioOutput")
             .field("device_id", &self.device_id)
-            .field("has_stream", &self._stream.is_some())
             .field("config", &self.config)
             .finish()
     }
@@ -38,7 +44,6 @@ impl CpalAudioOutput {
                 .map(|config| config.config());
 
             Self {
-                _stream: None,
                 device_id: device.name().unwrap_or_else(|_| "Unknown".to_string()),
                 config,
             }
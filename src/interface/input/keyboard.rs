@@ -0,0 +1,100 @@
+use crate::config::KeyboardMapping;
+use crate::error::{Error, Result};
+use crate::interface::input::GameBoyKey;
+use winit::event::VirtualKeyCode;
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        /// Key names accepted in the config, spelled like winit's `VirtualKeyCode` variants
+        const KEY_NAMES: &[(&str, VirtualKeyCode)] = &[$((stringify!($key), VirtualKeyCode::$key)),*];
+    };
+}
+
+key_names! {
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Up, Down, Left, Right,
+    Return, Space, Back, Tab, Escape, Insert, Delete, Home, End, PageUp, PageDown,
+    LShift, RShift, LControl, RControl, LAlt, RAlt,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+    Comma, Period, Slash, Backslash, Semicolon, Apostrophe, Grave, Minus, Equals,
+    LBracket, RBracket,
+}
+
+/// Look up a key by name, ignoring case
+pub fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name.trim()))
+        .map(|&(_, key)| key)
+}
+
+/// What a frontend hotkey does, for keys joypad buttons can't be bound to.
+/// Kept in sync with the hotkeys handled in `main.rs`.
+pub fn hotkey(key: VirtualKeyCode) -> Option<&'static str> {
+    use VirtualKeyCode::*;
+    match key {
+        Key1 | Key2 | Key3 | Key4 | Key5 | Key6 | Key7 | Key8 | Key9 => Some("save slots"),
+        F5 => Some("saving state"),
+        F8 => Some("loading state"),
+        Back => Some("rewind"),
+        Minus | Equals | Key0 => Some("speed control"),
+        P => Some("pause"),
+        N => Some("frame advance"),
+        _ => None,
+    }
+}
+
+/// Which keyboard key drives each joypad button
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(VirtualKeyCode, GameBoyKey)>,
+}
+
+impl KeyBindings {
+    /// Parse the key names of a config mapping. Unknown names, hotkeys and
+    /// keys bound twice are reported with the config field they came from.
+    pub fn from_mapping(mapping: &KeyboardMapping) -> Result<Self> {
+        let fields = [
+            ("up", &mapping.up, GameBoyKey::Up),
+            ("down", &mapping.down, GameBoyKey::Down),
+            ("left", &mapping.left, GameBoyKey::Left),
+            ("right", &mapping.right, GameBoyKey::Right),
+            ("a", &mapping.a, GameBoyKey::A),
+            ("b", &mapping.b, GameBoyKey::B),
+            ("start", &mapping.start, GameBoyKey::Start),
+            ("select", &mapping.select, GameBoyKey::Select),
+        ];
+
+        let mut bindings = Vec::with_capacity(fields.len());
+        let mut bound = Vec::with_capacity(fields.len());
+        for (field, name, button) in fields {
+            let invalid = |reason: String| {
+                Error::Config(format!("input.keyboard_mapping.{}: {}", field, reason))
+            };
+            let key = parse_key(name).ok_or_else(|| invalid(format!("unknown key {:?}", name)))?;
+            if let Some(action) = hotkey(key) {
+                return Err(invalid(format!("{:?} is reserved for {}", name, action)));
+            }
+            if let Some((_, other)) = bound.iter().find(|&&(other_key, _)| other_key == key) {
+                return Err(invalid(format!(
+                    "{:?} is already bound to input.keyboard_mapping.{}",
+                    name, other
+                )));
+            }
+            bound.push((key, field));
+            bindings.push((key, button));
+        }
+        Ok(Self { bindings })
+    }
+
+    /// The joypad button bound to `key`, if any
+    pub fn button(&self, key: VirtualKeyCode) -> Option<GameBoyKey> {
+        self.bindings
+            .iter()
+            .find(|&&(bound, _)| bound == key)
+            .map(|&(_, button)| button)
+    }
+}
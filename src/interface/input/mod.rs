@@ -1,3 +1,5 @@
 pub mod joypad;
+pub mod keyboard;
 pub mod simple_joypad;
 pub use self::joypad::{GameBoyKey, Joypad};
+pub use self::keyboard::KeyBindings;
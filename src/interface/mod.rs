@@ -3,7 +3,9 @@
 pub mod input;
 pub mod video;
 pub mod audio;
+pub mod timing;
 
 pub use input::{Joypad, GameBoyKey};
 pub use video::VideoInterface;
 pub use audio::AudioInterface;
+pub use timing::{FrameClock, Speed};
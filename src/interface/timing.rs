@@ -0,0 +1,180 @@
+use crate::core::cycles::{CyclesType, CPU_CLOCK, PPU_FRAME_CYCLES};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Frames the clock may fall behind before it gives up catching up
+const MAX_LAG_FRAMES: u64 = 5;
+
+/// Emulation speed relative to the real console, slowest first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Speed {
+    Half,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+    /// As fast as the host can run it
+    Uncapped,
+}
+
+impl Speed {
+    const ALL: [Speed; 5] = [
+        Speed::Half,
+        Speed::Normal,
+        Speed::Double,
+        Speed::Quadruple,
+        Speed::Uncapped,
+    ];
+
+    /// The speed as a fraction, `None` when uncapped
+    fn ratio(self) -> Option<(u64, u64)> {
+        match self {
+            Speed::Half => Some((1, 2)),
+            Speed::Normal => Some((1, 1)),
+            Speed::Double => Some((2, 1)),
+            Speed::Quadruple => Some((4, 1)),
+            Speed::Uncapped => None,
+        }
+    }
+
+    /// The next speed up, staying at uncapped
+    pub fn faster(self) -> Self {
+        Self::ALL[(self as usize + 1).min(Self::ALL.len() - 1)]
+    }
+
+    /// The next speed down, staying at half speed
+    pub fn slower(self) -> Self {
+        Self::ALL[(self as usize).saturating_sub(1)]
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Speed::Half => write!(f, "0.5x"),
+            Speed::Normal => write!(f, "1x"),
+            Speed::Double => write!(f, "2x"),
+            Speed::Quadruple => write!(f, "4x"),
+            Speed::Uncapped => write!(f, "Uncapped"),
+        }
+    }
+}
+
+/// Paces frames to the console's refresh rate (4194304 / 70224, about 59.73 Hz).
+///
+/// Deadlines are computed from the start time and the frame count rather than
+/// by adding a rounded frame duration each time, so timer jitter and rounding
+/// don't accumulate into drift.
+#[derive(Debug, Clone)]
+pub struct FrameClock {
+    start: Instant,
+    frames: u64,
+    frame_cycles: CyclesType,
+    clock_rate: CyclesType,
+    speed: Speed,
+}
+
+impl FrameClock {
+    pub fn new(frame_cycles: CyclesType, clock_rate: CyclesType) -> Self {
+        Self {
+            start: Instant::now(),
+            frames: 0,
+            frame_cycles,
+            clock_rate,
+            speed: Speed::Normal,
+        }
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Run at `speed` from `now` on. The next frame is due right away.
+    pub fn set_speed(&mut self, speed: Speed, now: Instant) {
+        self.speed = speed;
+        self.start = now;
+        self.frames = 0;
+    }
+
+    /// Time from the start until frame `frame` is due; every frame is due
+    /// immediately when uncapped
+    fn offset(&self, frame: u64) -> Duration {
+        let Some((numerator, denominator)) = self.speed.ratio() else {
+            return Duration::ZERO;
+        };
+        let nanos = frame as u128 * self.frame_cycles as u128 * 1_000_000_000;
+        let nanos = nanos * denominator as u128 / (self.clock_rate as u128 * numerator as u128);
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// When the next frame should run
+    pub fn next_deadline(&self) -> Instant {
+        self.start + self.offset(self.frames)
+    }
+
+    /// Whether the next frame is due at `now`; counts it as run if so. After a
+    /// long stall (a dragged window, a debugger) the clock restarts at `now`
+    /// instead of rushing through the missed frames.
+    pub fn frame_due(&mut self, now: Instant) -> bool {
+        if now < self.next_deadline() {
+            return false;
+        }
+        self.frames += 1;
+        if now > self.start + self.offset(self.frames + MAX_LAG_FRAMES) {
+            self.start = now;
+            self.frames = 1;
+        }
+        true
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new(PPU_FRAME_CYCLES, CPU_CLOCK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_clock_does_not_drift() {
+        let clock = FrameClock::default();
+        // 4194304 frames of 70224 cycles take exactly 70224 seconds
+        assert_eq!(
+            clock.offset(CPU_CLOCK as u64),
+            Duration::from_secs(PPU_FRAME_CYCLES as u64)
+        );
+        assert_eq!(clock.offset(1), Duration::from_nanos(16_742_706));
+    }
+
+    #[test]
+    fn test_frame_clock_resyncs_after_stall() {
+        let mut clock = FrameClock::default();
+        let start = clock.start;
+        assert!(clock.frame_due(start));
+        assert!(!clock.frame_due(start));
+
+        let stalled = start + Duration::from_secs(1);
+        assert!(clock.frame_due(stalled));
+        assert_eq!(clock.next_deadline(), stalled + clock.offset(1));
+    }
+
+    #[test]
+    fn test_frame_clock_speed() {
+        let mut clock = FrameClock::default();
+        let start = clock.start;
+        clock.set_speed(Speed::Double, start);
+        assert_eq!(clock.offset(2), Duration::from_nanos(16_742_706));
+        clock.set_speed(Speed::Normal.slower(), start);
+        assert_eq!(clock.speed(), Speed::Half);
+        assert_eq!(clock.offset(1), Duration::from_nanos(33_485_412));
+
+        clock.set_speed(Speed::Quadruple.faster().faster(), start);
+        assert_eq!(clock.speed(), Speed::Uncapped);
+        for _ in 0..10 {
+            assert!(clock.frame_due(start));
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::any::Any;
 use std::fmt::{self, Debug};
 use winit::window::Window;
 
-pub trait VideoInterface: Debug + Any {
+pub trait VideoInterface: Debug + Any + Send {
     fn update_frame(&mut self, frame_buffer: Vec<u8>);
     fn render(&mut self) -> Result<(), crate::error::Error>;
     fn resize(&mut self, new_width: u32, new_height: u32) -> Result<(), crate::error::Error>;
@@ -84,3 +84,23 @@ impl VideoInterface for PixelsDisplay {
         self
     }
 }
+
+// Discards every frame, for running without a window
+#[derive(Debug)]
+pub struct NullVideoOutput;
+
+impl VideoInterface for NullVideoOutput {
+    fn update_frame(&mut self, _frame_buffer: Vec<u8>) {}
+
+    fn render(&mut self) -> Result<(), crate::error::Error> {
+        Ok(())
+    }
+
+    fn resize(&mut self, _new_width: u32, _new_height: u32) -> Result<(), crate::error::Error> {
+        Ok(())
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
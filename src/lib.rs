@@ -1,8 +1,11 @@
 // Game Boy Emulator Main Module
 #![forbid(unsafe_code)]
 
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::core::bus::SystemBus;
+use crate::core::cycles::{CyclesType, PPU_FRAME_CYCLES};
+use crate::core::state::{self, SaveState, StateReader, StateWriter, STATE_VERSION};
+use crate::error::StateError;
+use std::path::PathBuf;
 
 // Core emulator modules
 pub mod core;
@@ -19,6 +22,12 @@ pub mod config;
 // Utility functions
 pub mod utils;
 
+// Headless accuracy test runner
+pub mod test_rom;
+
+// Rewind history
+pub mod rewind;
+
 // Debug features
 #[cfg(debug_assertions)]
 pub mod debugger;
@@ -28,98 +37,235 @@ pub mod debugger;
 pub mod tests;
 
 // Re-exports for public API
-// pub use crate::emulator::core::Emulator as Core;
 pub use error::{Error, Result};
 pub use interface::{audio::AudioInterface, input::joypad::Joypad, video::VideoInterface};
 
 // Re-export core modules for external use
+pub use crate::core::bus::{Bus, FlatBus};
+pub use crate::core::cpu::trace::{TraceCondition, Tracer};
 pub use crate::core::cpu::CPU;
+pub use crate::core::mmu::boot::{BootRom, Model};
 pub use crate::core::mmu::MMU;
 pub use crate::core::ppu::PPU;
+pub use crate::core::serial::{CaptureLink, NullLink, SerialLink, TcpLink};
+pub use crate::core::state::StateHeader;
+pub use crate::rewind::Rewind;
+pub use crate::test_rom::{TestReport, TestRom, TestStatus};
+
+/// Outcome of one `GameBoy::run_frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameReport {
+    /// T-cycles executed by this call
+    pub cycles: CyclesType,
+    /// T-cycles run past the frame boundary, taken off the next frame
+    pub overshoot: CyclesType,
+    /// The PPU entered V-Blank, so there is a new picture to show
+    pub frame_ready: bool,
+}
 
-/// Main structure of the GameBoy emulator
+/// Main structure of the GameBoy emulator, owning every component.
+/// The CPU drives the clock: each of its bus accesses advances the rest by one M-cycle.
 #[derive(Debug)]
 pub struct GameBoy {
-    mmu: Rc<RefCell<MMU>>,
-    ppu: PPU,
-    cpu: CPU,
-    render_count: u64,
+    // The CPU owns the bus, which owns the MMU, PPU and APU; the MMU owns the
+    // timer, serial port, OAM DMA and cartridge
+    cpu: CPU<SystemBus>,
+    overshoot: CyclesType,
 }
 
 impl GameBoy {
     pub fn new(
         video: Box<dyn VideoInterface>,
-        _audio: Option<Box<dyn AudioInterface>>,
+        audio: Option<Box<dyn AudioInterface>>,
     ) -> Result<Self> {
-        let mmu = Rc::new(RefCell::new(MMU::new())); // Don't initialize any LCD registers here - let ROM control everything
-                                                     // Removed logging for better performance
-
-        let ppu = PPU::new(Rc::clone(&mmu), video);
-        let cpu = CPU::new(Rc::clone(&mmu));
+        // Don't initialize any LCD registers here - let ROM control everything
+        let cpu = CPU::new(SystemBus::new(video, audio));
 
-        Ok(Self {
-            mmu,
-            ppu,
-            cpu,
-            render_count: 0,
-        })
+        Ok(Self { cpu, overshoot: 0 })
+    }
+    fn mmu_mut(&mut self) -> &mut MMU {
+        &mut self.cpu.bus_mut().mmu
     }
     pub fn load_rom(&mut self, rom_data: Vec<u8>) -> Result<()> {
-        let mut mmu = self.mmu.borrow_mut();
-        mmu.load_rom(&rom_data)?;
+        self.mmu_mut().load_rom(&rom_data)?;
 
         // Log ROM entry point for debugging
         if rom_data.len() > 0x100 {
-            println!("ROM Entry Point Instructions:");
+            log::debug!("ROM Entry Point Instructions:");
             for i in 0..8 {
                 if 0x100 + i < rom_data.len() {
-                    println!("  0x{:04X}: 0x{:02X}", 0x100 + i, rom_data[0x100 + i]);
+                    log::debug!("  0x{:04X}: 0x{:02X}", 0x100 + i, rom_data[0x100 + i]);
                 }
             }
         }
 
-        drop(mmu);
-        self.init_lcd_registers()?;
+        self.init_cpu_state()?;
 
         Ok(())
     }
-    /// Initialize only essential registers for ROM execution
-    fn init_lcd_registers(&mut self) -> Result<()> {
-        let mut mmu = self.mmu.borrow_mut();
+    /// Overlay a boot ROM dump on the cartridge; it runs from 0x0000 after `load_rom`
+    pub fn load_boot_rom(&mut self, data: Vec<u8>) -> Result<()> {
+        let mmu = self.mmu_mut();
+        let boot_rom = BootRom::new(data, mmu.model())?;
+        mmu.load_boot_rom(boot_rom);
+        Ok(())
+    }
+    /// Select the hardware model whose post-boot state is used without a boot ROM
+    pub fn set_model(&mut self, model: Model) {
+        self.mmu_mut().set_model(model);
+    }
+    /// Start the CPU in the boot ROM, or at 0x0100 with the model's post-boot registers
+    fn init_cpu_state(&mut self) -> Result<()> {
+        let mmu = self.mmu();
+        let (boot_rom_mapped, model) = (mmu.boot_rom_mapped(), mmu.model());
+        let header_checksum = mmu.header_checksum();
 
-        // Set only essential joypad register (pulled high, no buttons pressed)
-        // This is critical for Tetris - prevents soft reset
-        mmu.write_byte(0xFF00, 0xFF).ok(); // Joypad register - all buttons released        // Enable LCD and background for basic display
-        mmu.write_byte(0xFF40, 0x91).ok(); // LCDC: LCD=1, BG=1, BG tile map=0, BG tile data=1
-        mmu.write_byte(0xFF47, 0xE4).ok(); // BGP: Background palette
+        if boot_rom_mapped {
+            self.cpu.start_boot_rom()
+        } else {
+            self.cpu.apply_post_boot_state(model, header_checksum)
+        }
+    }
+    /// Run one video frame, see `run_frame`
+    pub fn step(&mut self) -> Result<()> {
+        self.run_frame().map(|_| ())
+    }
+    /// Run until the PPU enters V-Blank, or for a full 70224 T-cycle frame while
+    /// the LCD is off. The last instruction usually runs a few cycles past the
+    /// boundary; those count towards the next frame.
+    pub fn run_frame(&mut self) -> Result<FrameReport> {
+        let budget = PPU_FRAME_CYCLES - self.overshoot;
+        let mut cycles = 0;
+        // A V-Blank reached through `step_instruction` belongs to a frame already run
+        self.cpu.bus_mut().ppu.take_frame_ready();
+        let frame_ready = loop {
+            cycles += self.cpu.step()?;
+            if self.cpu.bus_mut().ppu.take_frame_ready() {
+                break true;
+            }
+            if cycles >= budget {
+                break false;
+            }
+        };
+        self.overshoot = cycles.saturating_sub(budget);
 
-        // Enable V-Blank interrupts by default
-        mmu.write_byte(0xFFFF, 0x01).ok(); // IE: Enable V-Blank interrupt
-        mmu.write_byte(0xFF0F, 0x00).ok(); // IF: Clear all interrupt flags
+        self.mmu_mut().flush_save_if_due()?;
 
-        // Let ROM set all LCD registers itself
-        println!("Essential registers initialized - ROM has full control with V-Blank enabled");
+        Ok(FrameReport {
+            cycles,
+            overshoot: self.overshoot,
+            frame_ready,
+        })
+    }
+    /// Run a single instruction (or interrupt dispatch) and return the T-cycles it took
+    pub fn step_instruction(&mut self) -> Result<CyclesType> {
+        self.cpu.step()
+    }
+    /// Stop sending samples to the audio output, e.g. while not running at 1x
+    pub fn set_audio_muted(&mut self, muted: bool) {
+        self.cpu.bus_mut().apu.set_muted(muted);
+    }
+    /// Samples queued in the audio backend, when it reports them
+    pub fn queued_audio_samples(&self) -> Option<usize> {
+        let output = self.cpu.bus().apu.audio_output.as_ref()?;
+        output.queued_samples()
+    }
+    pub fn cpu(&self) -> &CPU<SystemBus> {
+        &self.cpu
+    }
+    pub fn mmu(&self) -> &MMU {
+        &self.cpu.bus().mmu
+    }
+    pub fn ppu(&self) -> &PPU {
+        &self.cpu.bus().ppu
+    }
+    /// The last drawn picture, 160x144 pixels as 0xAARRGGBB
+    pub fn framebuffer(&self) -> &[u32] {
+        self.ppu().display.get_frame()
+    }
+    /// Snapshot the whole machine, with a thumbnail of the current picture.
+    /// Only `load_state` with the same ROM loaded can restore it.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mmu = self.mmu();
+        let header = StateHeader {
+            version: STATE_VERSION,
+            title: mmu.rom_title(),
+            checksum: mmu.global_checksum(),
+            thumbnail: state::thumbnail(self.framebuffer()),
+        };
 
-        // Removed system init logging for performance
+        let mut w = StateWriter::new();
+        header.write(&mut w);
+        let mut data = w.into_inner();
+        data.extend(self.snapshot());
+        data
+    }
+    /// Restore a `save_state` snapshot. States from another ROM or format
+    /// version are rejected; a corrupt one leaves the machine as it was.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<()> {
+        let mut r = StateReader::new(data);
+        let header = StateHeader::read(&mut r)?;
+        let mmu = self.mmu();
+        if header.title != mmu.rom_title() || header.checksum != mmu.global_checksum() {
+            return Err(StateError::WrongRom {
+                title: header.title,
+                checksum: header.checksum,
+            }
+            .into());
+        }
 
+        self.restore_snapshot(r.remaining())
+    }
+    /// The machine state alone, without the header and thumbnail `save_state`
+    /// adds. Cheap enough to take every frame, e.g. for rewind.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.cpu.save_state(&mut w);
+        w.u32(self.overshoot);
+        w.into_inner()
+    }
+    /// Restore a `snapshot` taken with the same ROM loaded; a corrupt one
+    /// leaves the machine as it was. The video output is not touched.
+    pub fn restore_snapshot(&mut self, data: &[u8]) -> Result<()> {
+        let backup = self.snapshot();
+        if let Err(e) = self.load_machine_state(&mut StateReader::new(data)) {
+            self.load_machine_state(&mut StateReader::new(&backup))?;
+            return Err(e);
+        }
         Ok(())
     }
-    pub fn step(&mut self) -> Result<()> {
-        // At 1 FPS, execute many more instructions per frame to complete ROM init loops
-        // ROM has multiple initialization phases that need to complete
-        for _ in 0..5000 {
-            let cpu_cycles = self.cpu.step()?;
-            self.ppu.step(cpu_cycles)?;
+    fn load_machine_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.cpu.load_state(r)?;
+        self.overshoot = r.u32()?;
+        if !r.is_empty() {
+            return Err(StateError::Corrupt.into());
         }
-
         Ok(())
     }
+    /// Log every executed instruction in Gameboy Doctor format; `None` turns it off
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        self.cpu.set_tracer(tracer)
+    }
+    /// Plug a link cable backend into the serial port, returning the old one
+    pub fn set_serial_link(&mut self, link: Box<dyn SerialLink>) -> Box<dyn SerialLink> {
+        self.mmu_mut().set_serial_link(link)
+    }
+    /// Load battery-backed cartridge RAM from `path` and keep it in sync with the cartridge
+    pub fn attach_save_file(&mut self, path: PathBuf) -> Result<()> {
+        self.mmu_mut().attach_save_file(path)
+    }
+    /// Write pending cartridge RAM changes to the save file, e.g. before exiting
+    pub fn flush_save(&mut self) -> Result<()> {
+        self.mmu_mut().flush_save()
+    }
     pub fn reset(&mut self) -> Result<()> {
-        let mut mmu = self.mmu.borrow_mut();
-        mmu.reset();
-        drop(mmu); // Release the borrow        // Reset CPU to initial state
-        self.cpu.reset()?; // Reset PPU
-        self.ppu.reset()?;
+        self.mmu_mut().flush_save()?;
+        // Reset MMU, PPU and APU
+        self.cpu.bus_mut().reset()?;
+
+        // Reset CPU to initial state
+        self.init_cpu_state()?;
+        self.overshoot = 0;
 
         // Removed reset logging for performance
 
@@ -127,41 +273,14 @@ impl GameBoy {
     }
 
     pub fn get_video_mut(&mut self) -> &mut dyn VideoInterface {
-        self.ppu.get_video_mut()
+        self.cpu.bus_mut().ppu.get_video_mut()
     }
 
     pub fn update_joypad_state(&mut self, joypad: &dyn Joypad) -> Result<()> {
-        self.mmu.borrow_mut().update_joypad_state(joypad);
+        self.mmu_mut().update_joypad_state(joypad);
         Ok(())
     }
     pub fn render(&mut self) -> Result<()> {
-        // Log VRAM status every few seconds for debugging
-        self.render_count += 1;
-        if self.render_count % 300 == 0 {
-            // Every ~5 seconds at 60 FPS
-            if let Ok(mmu) = self.mmu.try_borrow() {
-                let mut non_zero_count = 0;
-                for i in 0x8000..0x9000 {
-                    if mmu.read_byte(i).unwrap_or(0) != 0 {
-                        non_zero_count += 1;
-                    }
-                }
-                println!(
-                    "VRAM Status: {} non-zero bytes in tile data area",
-                    non_zero_count
-                );
-
-                // Check background map
-                let mut bg_map_count = 0;
-                for i in 0x9800..0x9C00 {
-                    if mmu.read_byte(i).unwrap_or(0) != 0 {
-                        bg_map_count += 1;
-                    }
-                }
-                println!("Background Map: {} non-zero entries", bg_map_count);
-            }
-        }
-
-        self.ppu.render()
+        self.cpu.bus_mut().ppu.render()
     }
 }
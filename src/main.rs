@@ -1,21 +1,39 @@
 use chrono;
 use gameboy_emulator::{
+    config::Config,
+    core::audio::SAMPLE_RATE,
+    core::mmu::save,
+    core::state::{self, THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH},
     error::{Error, HardwareError, Result},
-    interface::{audio::AudioInterface, input::simple_joypad::SimpleJoypad, video::PixelsDisplay},
-    GameBoy,
+    interface::{
+        audio::AudioInterface,
+        input::{simple_joypad::SimpleJoypad, Joypad, KeyBindings},
+        timing::{FrameClock, Speed},
+        video::PixelsDisplay,
+    },
+    test_rom::DEFAULT_TIMEOUT_FRAMES,
+    GameBoy, Rewind, StateHeader, TcpLink, TestRom, TestStatus, Tracer,
 };
 use std::fs::{self, File};
 use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use winit::{
     dpi::LogicalSize,
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
-const TARGET_FPS: u64 = 1;
-const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / TARGET_FPS);
+/// Audio to keep queued when the audio backend sets the pace (about 50 ms)
+const AUDIO_BUFFER_SAMPLES: usize = SAMPLE_RATE as usize / 20;
+/// How often to check the audio queue while it is full
+const AUDIO_POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// Shortest time between presented frames above 1x, so presenting doesn't cap the speed
+const RENDER_INTERVAL: Duration = Duration::from_micros(16_743);
+/// How long a save slot's thumbnail stays on screen
+const PREVIEW_TIME: Duration = Duration::from_secs(2);
+const SCREEN_WIDTH: usize = 160;
 
 // Simple audio interface implementation
 #[derive(Debug)]
@@ -32,7 +50,15 @@ fn main() -> Result<()> {
     initialize_logs()?;
 
     // Get ROM path from command line arguments or use default
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let config_path = take_option(&mut args, "--config")?;
+    let config_path = config_path.as_deref().map(Path::new);
+    if args.get(1).map(String::as_str) == Some("test-rom") {
+        // Test runs shouldn't leave a config file behind
+        return run_test_rom(&args[2..], &Config::read(config_path)?);
+    }
+    let mut config = Config::load(config_path)?;
+
     let rom_path = if args.len() > 1 {
         &args[1]
     } else {
@@ -42,7 +68,7 @@ fn main() -> Result<()> {
     // Ensure ROM file exists
     if !std::path::Path::new(rom_path).exists() {
         eprintln!("ROM file not found: {}", rom_path);
-        eprintln!("Usage: {} [rom_file]", args[0]);
+        eprintln!("Usage: {} [--config config.toml] [rom_file]", args[0]);
         eprintln!("Example: {} rom/tetris.gb", args[0]);
         return Err(Error::Hardware(HardwareError::Custom(
             "ROM file not found".to_string(),
@@ -76,10 +102,11 @@ fn main() -> Result<()> {
     println!("Cartridge Type: 0x{:02X}", rom_data[0x147]);
 
     // Initialize window and display
+    let scale = config.video.scale as f64;
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("Game Boy Emulator")
-        .with_inner_size(LogicalSize::new(160.0 * 3.0, 144.0 * 3.0))
+        .with_inner_size(LogicalSize::new(160.0 * scale, 144.0 * scale))
         .with_resizable(true)
         .build(&event_loop)
         .map_err(|e| Error::Hardware(HardwareError::Display(e.to_string())))?;
@@ -90,15 +117,50 @@ fn main() -> Result<()> {
     // Initialize Game Boy
     let mut gameboy = GameBoy::new(Box::new(video), Some(Box::new(DummyAudio)))?;
 
+    gameboy.set_model(config.system.model);
+    if config.system.bootrom_enabled {
+        let path = config.system.bootrom_path.as_deref().ok_or_else(|| {
+            Error::Config("bootrom_enabled is set but bootrom_path is missing".to_string())
+        })?;
+        println!("Loading boot ROM: {}", path);
+        gameboy.load_boot_rom(std::fs::read(path)?)?;
+    }
+
+    if let Some(path) = &config.system.trace_path {
+        println!("Tracing instructions to {}", path);
+        gameboy.set_tracer(Some(Tracer::to_file(path)?));
+    }
+
+    if let Some(addr) = &config.system.link_listen {
+        println!("Waiting for a link cable connection on {}...", addr);
+        gameboy.set_serial_link(Box::new(TcpLink::listen(addr.as_str())?));
+    } else if let Some(addr) = &config.system.link_connect {
+        println!("Connecting link cable to {}", addr);
+        gameboy.set_serial_link(Box::new(TcpLink::connect(addr.as_str())?));
+    }
+
     // Load ROM and start simulation
     println!("Loading ROM...");
     gameboy.load_rom(rom_data)?;
     println!("ROM loaded successfully");
 
-    let joypad = SimpleJoypad::new();
-    let mut last_frame = Instant::now();
+    // Battery-backed cartridge RAM lives in a .sav file next to the ROM unless configured otherwise
+    let save_dir = config.system.save_dir.as_deref().map(Path::new);
+    gameboy.attach_save_file(save::save_path(Path::new(rom_path), save_dir))?;
+
+    let mut slots = StateSlots::new(&config.system.save_state_path, rom_path);
+    let rewind_capacity = config.system.rewind_buffer_mb as usize * 1024 * 1024;
+    let mut rewind = Rewind::new(rewind_capacity, config.system.rewind_interval);
+    let mut rewinding = false;
+    let bindings = KeyBindings::from_mapping(&config.input.keyboard_mapping)?;
+    let mut joypad = SimpleJoypad::new();
+    let mut clock = FrameClock::default();
+    let mut paused = false;
+    let mut advance = false;
+    let mut last_render = Instant::now();
     let mut fps_timer = Instant::now();
     let mut frames = 0;
+    let mut fps = 0;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -108,8 +170,79 @@ fn main() -> Result<()> {
                 event: WindowEvent::CloseRequested,
                 ..
             } => {
+                if let Err(e) = gameboy.flush_save() {
+                    eprintln!("Failed to write save file: {}", e);
+                }
                 *control_flow = ControlFlow::Exit;
             }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(keycode),
+                                state,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(key) = bindings.button(keycode) {
+                    match state {
+                        ElementState::Pressed => joypad.press_key(key),
+                        ElementState::Released => joypad.release_key(key),
+                    }
+                    // Forward immediately so the joypad interrupt fires on the press
+                    if let Err(e) = gameboy.update_joypad_state(&joypad) {
+                        eprintln!("Failed to update joypad: {}", e);
+                    }
+                } else if keycode == VirtualKeyCode::Back {
+                    // Hold Backspace to rewind
+                    rewinding = state == ElementState::Pressed && rewind_capacity > 0;
+                } else if state == ElementState::Pressed {
+                    // 1-9 pick a save slot, F5 saves to it and F8 loads it.
+                    // -/= change the speed and 0 resets it, P pauses and N advances a frame.
+                    let result = match keycode {
+                        VirtualKeyCode::Minus | VirtualKeyCode::Equals | VirtualKeyCode::Key0 => {
+                            let speed = match keycode {
+                                VirtualKeyCode::Minus => clock.speed().slower(),
+                                VirtualKeyCode::Equals => clock.speed().faster(),
+                                _ => Speed::Normal,
+                            };
+                            clock.set_speed(speed, Instant::now());
+                            // Sound only plays at 1x instead of crackling at other speeds
+                            gameboy.set_audio_muted(speed != Speed::Normal);
+                            Ok(())
+                        }
+                        VirtualKeyCode::P => {
+                            paused = !paused;
+                            Ok(())
+                        }
+                        VirtualKeyCode::N => {
+                            paused = true;
+                            advance = true;
+                            Ok(())
+                        }
+                        VirtualKeyCode::F5 => slots.save(&gameboy),
+                        VirtualKeyCode::F8 => {
+                            // History from before the load would rewind into another timeline
+                            rewind.clear();
+                            slots.load(&mut gameboy)
+                        }
+                        _ => {
+                            if let Some(slot) = slot_key(keycode) {
+                                slots.select(slot);
+                            }
+                            Ok(())
+                        }
+                    };
+                    if let Err(e) = result {
+                        eprintln!("Save state failed: {}", e);
+                    }
+                    window.set_title(&window_title(fps, clock.speed(), paused, slots.current));
+                }
+            }
             Event::WindowEvent {
                 event: WindowEvent::Resized(new_size),
                 ..
@@ -119,46 +252,96 @@ fn main() -> Result<()> {
                     .get_video_mut()
                     .resize(new_size.width, new_size.height)
                     .ok();
+
+                // Remember the nearest whole scale for the next start. It only
+                // changes at whole steps, so a drag writes the file a few times at most.
+                let size = new_size.to_logical::<f64>(window.scale_factor());
+                let scale = (size.width / 160.0).round() as u32;
+                if scale != config.video.scale && (1..=10).contains(&scale) {
+                    config.video.scale = scale;
+                    if let Err(e) = config.save() {
+                        eprintln!("Failed to write config: {}", e);
+                    }
+                }
             }
             Event::MainEventsCleared => {
                 let now = Instant::now();
-                let elapsed = now.duration_since(last_frame);
 
-                // FPS calculation
-                frames += 1;
-                if fps_timer.elapsed() >= Duration::from_secs(1) {
-                    window.set_title(&format!("Game Boy Emulator - {} FPS", frames));
-                    frames = 0;
-                    fps_timer = now;
-                }
+                // Pace to the audio queue when the backend reports it, else to 59.73 Hz.
+                // Rewinding and other speeds produce no audio, so they go by the clock.
+                let queued = gameboy
+                    .queued_audio_samples()
+                    .filter(|_| !rewinding && clock.speed() == Speed::Normal);
+                let (frame_due, next_check) = match queued {
+                    Some(queued) => (queued < AUDIO_BUFFER_SAMPLES, now + AUDIO_POLL_INTERVAL),
+                    None => {
+                        let due = clock.frame_due(now);
+                        (due, clock.next_deadline())
+                    }
+                };
+                // While paused, frames only run one at a time on request
+                let frame_due = if paused && !rewinding {
+                    std::mem::take(&mut advance)
+                } else {
+                    frame_due
+                };
 
-                // Frame timing
-                if elapsed >= FRAME_TIME {
+                if frame_due {
                     // Update joypad state
                     if let Err(e) = gameboy.update_joypad_state(&joypad) {
                         eprintln!("Failed to update joypad: {}", e);
                     }
 
-                    // Run emulation
-                    if let Err(e) = gameboy.step() {
+                    // Run emulation, or step back through the rewind history
+                    let result = if rewinding {
+                        rewind.rewind(&mut gameboy).map(|_| ())
+                    } else {
+                        gameboy.run_frame().map(|_| {
+                            if rewind_capacity > 0 {
+                                rewind.record(&gameboy);
+                            }
+                        })
+                    };
+                    if let Err(e) = result {
                         eprintln!("Error during emulation: {}", e);
+                        gameboy.flush_save().ok();
                         *control_flow = ControlFlow::Exit;
                         return;
                     }
 
-                    // Render frame
-                    if let Err(e) = gameboy.render() {
-                        eprintln!("Error during render: {}", e);
-                        *control_flow = ControlFlow::Exit;
-                        return;
+                    // Render frame, with the save slot preview on top while it shows.
+                    // Above 1x, frames the screen couldn't show anyway are skipped.
+                    if clock.speed() <= Speed::Normal || now - last_render >= RENDER_INTERVAL {
+                        last_render = now;
+                        let rendered = match slots.overlay(gameboy.framebuffer()) {
+                            Some(frame) => {
+                                let video = gameboy.get_video_mut();
+                                video.update_frame(frame);
+                                video.render()
+                            }
+                            None => gameboy.render(),
+                        };
+                        if let Err(e) = rendered {
+                            eprintln!("Error during render: {}", e);
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+
+                        window.request_redraw();
                     }
 
-                    window.request_redraw();
-                    last_frame = now;
-                } else {
-                    // Wait for next frame
-                    *control_flow = ControlFlow::WaitUntil(last_frame + FRAME_TIME);
+                    // FPS calculation, in emulated frames
+                    frames += 1;
+                }
+                if fps_timer.elapsed() >= Duration::from_secs(1) {
+                    fps = frames;
+                    window.set_title(&window_title(fps, clock.speed(), paused, slots.current));
+                    frames = 0;
+                    fps_timer = now;
                 }
+
+                // Wait for next frame
+                *control_flow = ControlFlow::WaitUntil(next_check);
             }
             _ => (),
         }
@@ -168,6 +351,165 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Remove `flag` and the value after it from `args`, returning the value
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<String>> {
+    let Some(index) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    if index + 1 >= args.len() {
+        return Err(Error::Config(format!("{} needs a value", flag)));
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Ok(Some(value))
+}
+
+/// `test-rom <path> [--timeout-frames N]`: run an accuracy test ROM without a
+/// window and exit with 0 when it passes, 1 when it fails and 2 on timeout
+fn run_test_rom(args: &[String], config: &Config) -> Result<()> {
+    let usage = || Error::Config("usage: test-rom <path> [--timeout-frames N]".to_string());
+    let mut path = None;
+    let mut timeout_frames = DEFAULT_TIMEOUT_FRAMES;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--timeout-frames" => {
+                timeout_frames = args
+                    .next()
+                    .and_then(|frames| frames.parse().ok())
+                    .ok_or_else(usage)?;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(usage()),
+        }
+    }
+    let path = path.ok_or_else(usage)?;
+
+    let report = TestRom::from_file(path)?
+        .model(config.system.model)
+        .timeout_frames(timeout_frames)
+        .run()?;
+
+    if !report.serial_output.is_empty() {
+        println!("{}", report.serial_output.trim_end());
+    }
+    println!("{}: {}", path, report);
+    std::process::exit(match report.status {
+        TestStatus::Passed => 0,
+        TestStatus::Failed(_) => 1,
+        TestStatus::TimedOut => 2,
+    })
+}
+
+/// Window title with the FPS counter, the speed and the save slot
+fn window_title(fps: u32, speed: Speed, paused: bool, slot: u8) -> String {
+    let speed = if paused {
+        "Paused".to_string()
+    } else {
+        speed.to_string()
+    };
+    format!(
+        "Game Boy Emulator - {} FPS - {} - Slot {}",
+        fps, speed, slot
+    )
+}
+
+/// Number keys 1-9 select save slots
+fn slot_key(keycode: VirtualKeyCode) -> Option<u8> {
+    let slot = match keycode {
+        VirtualKeyCode::Key1 => 1,
+        VirtualKeyCode::Key2 => 2,
+        VirtualKeyCode::Key3 => 3,
+        VirtualKeyCode::Key4 => 4,
+        VirtualKeyCode::Key5 => 5,
+        VirtualKeyCode::Key6 => 6,
+        VirtualKeyCode::Key7 => 7,
+        VirtualKeyCode::Key8 => 8,
+        VirtualKeyCode::Key9 => 9,
+        _ => return None,
+    };
+    Some(slot)
+}
+
+/// Numbered save state slots for the running ROM, kept as `<rom>.ss<N>` files
+struct StateSlots {
+    dir: PathBuf,
+    rom_path: PathBuf,
+    current: u8,
+    preview: Option<(Vec<u32>, Instant)>, // Thumbnail and when to stop showing it
+}
+
+impl StateSlots {
+    fn new(dir: &str, rom_path: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            rom_path: PathBuf::from(rom_path),
+            current: 1,
+            preview: None,
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        state::slot_path(&self.rom_path, &self.dir, self.current)
+    }
+
+    /// Switch slots and show the thumbnail of what the slot holds
+    fn select(&mut self, slot: u8) {
+        self.current = slot;
+        self.preview = None;
+        let header = fs::read(self.path()).map(|data| StateHeader::from_state(&data));
+        match header {
+            Ok(Ok(header)) => {
+                println!("Slot {}: {}", slot, header.title);
+                self.preview = Some((header.thumbnail, Instant::now() + PREVIEW_TIME));
+            }
+            Ok(Err(e)) => println!("Slot {}: {}", slot, e),
+            Err(_) => println!("Slot {} is empty", slot),
+        }
+    }
+
+    fn save(&mut self, gameboy: &GameBoy) -> Result<()> {
+        let data = gameboy.save_state();
+        save::write_atomic(&self.path(), &data)?;
+        println!("Saved state to slot {}", self.current);
+        let header = StateHeader::from_state(&data)?;
+        self.preview = Some((header.thumbnail, Instant::now() + PREVIEW_TIME));
+        Ok(())
+    }
+
+    fn load(&mut self, gameboy: &mut GameBoy) -> Result<()> {
+        gameboy.load_state(&fs::read(self.path())?)?;
+        println!("Loaded state from slot {}", self.current);
+        self.preview = None;
+        Ok(())
+    }
+
+    /// `frame` as video output bytes with the thumbnail framed in the top
+    /// right corner, while a preview is showing
+    fn overlay(&mut self, frame: &[u32]) -> Option<Vec<u8>> {
+        let (thumbnail, until) = self.preview.as_ref()?;
+        if Instant::now() >= *until {
+            self.preview = None;
+            return None;
+        }
+
+        let mut frame = frame.to_vec();
+        let left = SCREEN_WIDTH - THUMBNAIL_WIDTH - 2;
+        for y in 0..THUMBNAIL_HEIGHT + 2 {
+            for x in 0..THUMBNAIL_WIDTH + 2 {
+                let inside =
+                    (1..=THUMBNAIL_WIDTH).contains(&x) && (1..=THUMBNAIL_HEIGHT).contains(&y);
+                frame[y * SCREEN_WIDTH + left + x] = if inside {
+                    thumbnail[(y - 1) * THUMBNAIL_WIDTH + x - 1]
+                } else {
+                    0xFF000000 // Black border
+                };
+            }
+        }
+        Some(frame.iter().flat_map(|pixel| pixel.to_ne_bytes()).collect())
+    }
+}
+
 fn initialize_logs() -> Result<()> {
     println!("Creating log directory and files...");
     let _ = fs::create_dir_all("logs");
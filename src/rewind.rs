@@ -0,0 +1,243 @@
+// Rewind history
+//
+// Snapshots are kept as the newest one in full plus a chain of deltas, each
+// turning a snapshot back into the one recorded before it. WRAM, VRAM and the
+// picture change little from frame to frame, so a delta is the XOR of two
+// snapshots with its zero runs squeezed out. Dropping the oldest delta forgets
+// the oldest snapshot without touching the rest, which keeps the history under
+// its memory cap.
+
+use crate::error::{Result, StateError};
+use crate::GameBoy;
+use std::collections::VecDeque;
+
+/// Fixed-memory history of machine snapshots that can be played backwards
+#[derive(Debug)]
+pub struct Rewind {
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    delta_bytes: usize,
+    capacity: usize,
+    interval: u32,
+    frames: u32, // Frames run since `latest` was taken
+    hold: u32,   // Ticks left before rewinding past the restored snapshot
+}
+
+impl Rewind {
+    /// Keep at most `capacity` bytes of history, one snapshot every `interval` frames
+    pub fn new(capacity: usize, interval: u32) -> Self {
+        Self {
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+            capacity,
+            interval: interval.max(1),
+            frames: 0,
+            hold: 0,
+        }
+    }
+
+    /// Bytes of history held
+    pub fn memory_used(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.delta_bytes
+    }
+
+    /// Snapshots that can be stepped back through
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+        self.frames = 0;
+        self.hold = 0;
+    }
+
+    /// Call after every emulated frame; takes a snapshot every `interval` frames
+    pub fn record(&mut self, gameboy: &GameBoy) {
+        self.hold = 0;
+        self.frames += 1;
+        if self.latest.is_none() || self.frames >= self.interval {
+            self.push(gameboy.snapshot());
+        }
+    }
+
+    /// Add the newest snapshot, forgetting the oldest ones to stay under the cap
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        if let Some(previous) = &self.latest {
+            let delta = encode_delta(previous, &snapshot);
+            self.delta_bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(snapshot);
+        self.frames = 0;
+
+        while self.memory_used() > self.capacity {
+            match self.deltas.pop_front() {
+                Some(delta) => self.delta_bytes -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Take the newest snapshot and make the one before it the newest.
+    /// The oldest snapshot is kept, so this keeps returning it at the end.
+    pub fn step_back(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some(latest) = self.latest.as_mut() else {
+            return Ok(None);
+        };
+        let snapshot = latest.clone();
+        if let Some(delta) = self.deltas.pop_back() {
+            self.delta_bytes -= delta.len();
+            *latest = apply_delta(latest, &delta)?;
+            self.frames = self.interval;
+        } else {
+            self.frames = 0;
+        }
+        Ok(Some(snapshot))
+    }
+
+    /// Call once per frame tick instead of running a frame while rewinding.
+    /// Each snapshot stays up for the `interval` frames it stood for, so
+    /// history plays backwards at normal speed. Returns false once the oldest
+    /// snapshot has been reached.
+    pub fn rewind(&mut self, gameboy: &mut GameBoy) -> Result<bool> {
+        if self.hold > 0 {
+            self.hold -= 1;
+            return Ok(true);
+        }
+        // A snapshot taken this frame is what's on screen, start one further back
+        if self.frames == 0 && !self.deltas.is_empty() {
+            self.step_back()?;
+        }
+        let more = !self.deltas.is_empty();
+        match self.step_back()? {
+            Some(snapshot) => gameboy.restore_snapshot(&snapshot)?,
+            None => return Ok(false),
+        }
+        self.hold = self.interval - 1;
+        Ok(more)
+    }
+}
+
+/// Encode `target` against `base`: the target length, then runs of
+/// (matching bytes, differing bytes, XOR of the differing bytes)
+fn encode_delta(target: &[u8], base: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, target.len());
+
+    let xor = |i: usize| target[i] ^ base.get(i).copied().unwrap_or(0);
+    let mut i = 0;
+    while i < target.len() {
+        let start = i;
+        while i < target.len() && xor(i) == 0 {
+            i += 1;
+        }
+        write_varint(&mut delta, i - start);
+
+        let start = i;
+        while i < target.len() && xor(i) != 0 {
+            i += 1;
+        }
+        write_varint(&mut delta, i - start);
+        delta.extend((start..i).map(xor));
+    }
+    delta
+}
+
+/// Rebuild the target of `encode_delta` from its base
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos)?;
+    let mut target: Vec<u8> = (0..len)
+        .map(|i| base.get(i).copied().unwrap_or(0))
+        .collect();
+
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos)?;
+        let count = read_varint(delta, &mut pos)?;
+        let bytes = delta.get(pos..pos + count).ok_or(StateError::Corrupt)?;
+        let out = target.get_mut(i..i + count).ok_or(StateError::Corrupt)?;
+        for (out, byte) in out.iter_mut().zip(bytes) {
+            *out ^= byte;
+        }
+        pos += count;
+        i += count;
+    }
+    Ok(target)
+}
+
+/// LEB128: 7 bits per byte, high bit set on all but the last
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<usize> {
+    let mut value = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let byte = *data.get(*pos).ok_or(StateError::Corrupt)?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(StateError::Corrupt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_round_trip() {
+        let base: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut target = base.clone();
+        target[3] ^= 0xFF;
+        target[500..520].fill(0);
+        target.extend_from_slice(&[1, 2, 3]);
+
+        let delta = encode_delta(&target, &base);
+        assert!(delta.len() < 40);
+        assert_eq!(apply_delta(&base, &delta).unwrap(), target);
+        // Shrinking works too
+        let delta = encode_delta(&base[..10], &target);
+        assert_eq!(apply_delta(&target, &delta).unwrap(), &base[..10]);
+        assert!(apply_delta(&base, &delta[..delta.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_history_steps_back_and_stays_under_cap() {
+        let snapshot = |n: u8| {
+            let mut data = vec![0u8; 256];
+            data[n as usize] = n;
+            data
+        };
+        // Room for the newest snapshot and three deltas
+        let capacity = 256 + 3 * encode_delta(&snapshot(1), &snapshot(2)).len();
+        let mut rewind = Rewind::new(capacity, 1);
+        for n in 1..=10 {
+            rewind.push(snapshot(n));
+        }
+        assert!(rewind.memory_used() <= capacity);
+        assert_eq!(rewind.len(), 4);
+
+        for n in (7..=10).rev() {
+            assert_eq!(rewind.step_back().unwrap(), Some(snapshot(n)));
+        }
+        // The oldest snapshot stays
+        assert_eq!(rewind.step_back().unwrap(), Some(snapshot(7)));
+        assert_eq!(rewind.len(), 1);
+    }
+}
@@ -0,0 +1,210 @@
+// Headless runner for Blargg and Mooneye accuracy test ROMs
+
+use crate::core::cycles::PPU_FRAME_CYCLES;
+use crate::core::mmu::boot::Model;
+use crate::core::serial::CaptureLink;
+use crate::error::Result;
+use crate::interface::audio::NullAudioOutput;
+use crate::interface::video::NullVideoOutput;
+use crate::GameBoy;
+use std::fmt;
+use std::path::Path;
+
+/// Frames to run before giving up; cpu_instrs needs about 3300 on DMG
+pub const DEFAULT_TIMEOUT_FRAMES: u64 = 7200;
+
+/// `LD B,B`, the software breakpoint Mooneye tests hit once they finish
+const MOONEYE_BREAKPOINT: u8 = 0x40;
+/// B, C, D, E, H, L after a passing Mooneye test
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// Written to every register by a failing Mooneye test
+const MOONEYE_FAIL: u8 = 0x42;
+
+/// Bytes at 0xA001-0xA003 once a Blargg test reports through cartridge RAM
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+/// Status byte at 0xA000 while the test is still running
+const BLARGG_RUNNING: u8 = 0x80;
+
+/// How a test ROM finished
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    /// Carries the reason reported by the ROM, or why the run was abandoned
+    Failed(String),
+    /// No verdict within the frame limit
+    TimedOut,
+}
+
+/// Which convention the verdict was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestProtocol {
+    /// "Passed"/"Failed" printed over the serial port
+    BlarggSerial,
+    /// Status and text in cartridge RAM at 0xA000
+    BlarggMemory,
+    /// Fibonacci registers at `LD B,B`
+    Mooneye,
+}
+
+/// Outcome of one test ROM run
+#[derive(Debug, Clone)]
+pub struct TestReport {
+    pub status: TestStatus,
+    pub protocol: Option<TestProtocol>,
+    /// Emulated frames run before the verdict
+    pub frames: u64,
+    /// Everything the ROM sent over the serial port
+    pub serial_output: String,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> bool {
+        self.status == TestStatus::Passed
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            TestStatus::Passed => write!(f, "passed")?,
+            TestStatus::Failed(reason) => write!(f, "failed: {}", reason)?,
+            TestStatus::TimedOut => write!(f, "timed out")?,
+        }
+        write!(f, " after {} frames", self.frames)?;
+        if let Some(protocol) = self.protocol {
+            write!(f, " ({:?})", protocol)?;
+        }
+        Ok(())
+    }
+}
+
+/// A test ROM to run without a window or audio output
+#[derive(Debug, Clone)]
+pub struct TestRom {
+    rom: Vec<u8>,
+    timeout_frames: u64,
+    model: Model,
+}
+
+impl TestRom {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            timeout_frames: DEFAULT_TIMEOUT_FRAMES,
+            model: Model::Dmg,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(std::fs::read(path)?))
+    }
+
+    /// Give up after this many emulated frames
+    pub fn timeout_frames(mut self, frames: u64) -> Self {
+        self.timeout_frames = frames;
+        self
+    }
+
+    /// Hardware model whose post-boot state the test starts from
+    pub fn model(mut self, model: Model) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Run until the ROM reports a verdict, locks up or times out
+    pub fn run(self) -> Result<TestReport> {
+        let video = Box::new(NullVideoOutput);
+        let mut gameboy = GameBoy::new(video, Some(Box::new(NullAudioOutput)))?;
+        gameboy.set_model(self.model);
+        let serial = CaptureLink::new();
+        gameboy.set_serial_link(Box::new(serial.clone()));
+        gameboy.load_rom(self.rom)?;
+
+        let timeout = self.timeout_frames * PPU_FRAME_CYCLES as u64;
+        let mut cycles: u64 = 0;
+        let mut next_frame = PPU_FRAME_CYCLES as u64;
+        let report = |status, protocol, cycles: u64| TestReport {
+            status,
+            protocol,
+            frames: cycles / PPU_FRAME_CYCLES as u64,
+            serial_output: serial.text(),
+        };
+
+        while cycles < timeout {
+            let pc = gameboy.cpu().registers().get_pc();
+            let breakpoint = gameboy.mmu().read_byte(pc)? == MOONEYE_BREAKPOINT;
+            cycles += gameboy.step_instruction()? as u64;
+
+            // Skip it if an interrupt was dispatched instead of the instruction
+            if breakpoint && gameboy.cpu().registers().get_pc() == pc.wrapping_add(1) {
+                if let Some(status) = mooneye_status(&gameboy) {
+                    return Ok(report(status, Some(TestProtocol::Mooneye), cycles));
+                }
+            }
+
+            if gameboy.cpu().is_locked() {
+                let status = TestStatus::Failed(format!("CPU locked up at 0x{:04X}", pc));
+                return Ok(report(status, None, cycles));
+            }
+
+            // The slower checks only run once per frame
+            if cycles >= next_frame {
+                next_frame += PPU_FRAME_CYCLES as u64;
+                if let Some(status) = blargg_serial_status(&serial.text()) {
+                    return Ok(report(status, Some(TestProtocol::BlarggSerial), cycles));
+                }
+                if let Some(status) = blargg_memory_status(&gameboy) {
+                    return Ok(report(status, Some(TestProtocol::BlarggMemory), cycles));
+                }
+            }
+        }
+
+        Ok(report(TestStatus::TimedOut, None, cycles))
+    }
+}
+
+fn mooneye_status(gameboy: &GameBoy) -> Option<TestStatus> {
+    let r = gameboy.cpu().registers();
+    let registers = [r.b, r.c, r.d, r.e, r.h, r.l];
+    if registers == MOONEYE_PASS {
+        Some(TestStatus::Passed)
+    } else if registers.iter().all(|&value| value == MOONEYE_FAIL) {
+        Some(TestStatus::Failed("registers hold 0x42".to_string()))
+    } else {
+        // Some tests use LD B,B as a plain breakpoint before they finish
+        None
+    }
+}
+
+fn blargg_serial_status(output: &str) -> Option<TestStatus> {
+    if output.contains("Passed") {
+        Some(TestStatus::Passed)
+    } else if output.contains("Failed") {
+        Some(TestStatus::Failed(output.trim().to_string()))
+    } else {
+        None
+    }
+}
+
+fn blargg_memory_status(gameboy: &GameBoy) -> Option<TestStatus> {
+    let mmu = gameboy.mmu();
+    let ram = &mmu.external_ram;
+    if ram.get(1..4)? != BLARGG_SIGNATURE || ram[0] == BLARGG_RUNNING {
+        return None;
+    }
+
+    // Zero-terminated text follows the signature
+    let text: Vec<u8> = ram[4..]
+        .iter()
+        .take_while(|&&byte| byte != 0)
+        .copied()
+        .collect();
+    let text = String::from_utf8_lossy(&text).trim().to_string();
+    match ram[0] {
+        0 => Some(TestStatus::Passed),
+        code => Some(TestStatus::Failed(format!(
+            "result code {}: {}",
+            code, text
+        ))),
+    }
+}
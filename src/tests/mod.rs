@@ -1 +1,248 @@
 // Integration test module for cross-module tests
+
+mod single_step;
+
+use crate::core::cycles::{Tick, CYCLES_1, PPU_FRAME_CYCLES};
+use crate::error::{Error, StateError};
+use crate::interface::video::NullVideoOutput;
+use crate::test_rom::{TestProtocol, TestRom, TestStatus};
+use crate::{GameBoy, Rewind};
+
+/// 32KB ROM-only cartridge with `program` at the 0x0100 entry point
+fn rom_with_program(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x100 + program.len()].copy_from_slice(program);
+    rom
+}
+
+/// Post-boot machine with `rom` inserted
+fn gameboy_with_rom(rom: Vec<u8>) -> GameBoy {
+    let mut gameboy = GameBoy::new(Box::new(NullVideoOutput), None).unwrap();
+    gameboy.load_rom(rom).unwrap();
+    gameboy
+}
+
+/// Post-boot machine running `program` from a ROM-only cartridge
+fn gameboy_with_program(program: &[u8]) -> GameBoy {
+    gameboy_with_rom(rom_with_program(program))
+}
+
+/// inc a; ld [$C000], a; jr -6: a counter in work RAM that changes every loop
+const COUNTER_PROGRAM: [u8; 6] = [0x3C, 0xEA, 0x00, 0xC0, 0x18, 0xFA];
+
+#[test]
+fn test_rom_runner_detects_mooneye_pass() {
+    // ld b, 3; ld c, 5; ld d, 8; ld e, 13; ld h, 21; ld l, 34; ld b, b; jr @
+    let rom = rom_with_program(&[
+        0x06, 3, 0x0E, 5, 0x16, 8, 0x1E, 13, 0x26, 21, 0x2E, 34, 0x40, 0x18, 0xFE,
+    ]);
+    let report = TestRom::new(rom).run().unwrap();
+    assert_eq!(report.status, TestStatus::Passed);
+    assert_eq!(report.protocol, Some(TestProtocol::Mooneye));
+    assert_eq!(report.frames, 0);
+}
+
+#[test]
+fn test_rom_runner_reads_blargg_serial_output() {
+    let mut program = Vec::new();
+    for &byte in b"Failed #2" {
+        // ld a, byte; ldh [SB], a; ld a, $81; ldh [SC], a
+        program.extend_from_slice(&[0x3E, byte, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02]);
+    }
+    program.extend_from_slice(&[0x18, 0xFE]);
+
+    let report = TestRom::new(rom_with_program(&program)).run().unwrap();
+    assert_eq!(report.status, TestStatus::Failed("Failed #2".to_string()));
+    assert_eq!(report.protocol, Some(TestProtocol::BlarggSerial));
+    assert_eq!(report.serial_output, "Failed #2");
+
+    let hang = TestRom::new(rom_with_program(&[0x18, 0xFE]))
+        .timeout_frames(3)
+        .run()
+        .unwrap();
+    assert_eq!(hang.status, TestStatus::TimedOut);
+    assert_eq!(hang.frames, 3);
+}
+
+#[test]
+fn test_gameboy_runs_on_worker_thread() {
+    // ld a, $42; jr @
+    let mut gameboy = gameboy_with_program(&[0x3E, 0x42, 0x18, 0xFE]);
+
+    let worker = std::thread::spawn(move || {
+        for _ in 0..10 {
+            gameboy.step_instruction()?;
+        }
+        Ok::<_, crate::Error>(gameboy)
+    });
+    let gameboy = worker.join().unwrap().unwrap();
+    assert_eq!(gameboy.cpu().registers().a, 0x42);
+    assert_eq!(gameboy.cpu().registers().get_pc(), 0x0102);
+}
+
+#[test]
+fn test_run_frame_stops_at_vblank_and_carries_overshoot() {
+    // jr @, with the LCD on from the post-boot state
+    let mut gameboy = gameboy_with_program(&[0x18, 0xFE]);
+    let mut previous = gameboy.run_frame().unwrap();
+    assert!(previous.frame_ready);
+    for _ in 0..3 {
+        let frame = gameboy.run_frame().unwrap();
+        assert!(frame.frame_ready);
+        assert_eq!(
+            frame.cycles,
+            PPU_FRAME_CYCLES - previous.overshoot + frame.overshoot
+        );
+        previous = frame;
+    }
+
+    // ld a, 0; ldh [LCDC], a; jr @: no V-Blank, so the frame runs on cycles alone
+    let mut gameboy = gameboy_with_program(&[0x3E, 0x00, 0xE0, 0x40, 0x18, 0xFE]);
+    let frame = gameboy.run_frame().unwrap();
+    assert!(!frame.frame_ready);
+    assert_eq!(frame.cycles, PPU_FRAME_CYCLES + frame.overshoot);
+}
+
+#[test]
+fn test_run_frame_ignores_v_blank_reached_by_single_steps() {
+    // jr @, with the LCD on from the post-boot state
+    let mut gameboy = gameboy_with_program(&[0x18, 0xFE]);
+    while gameboy.ppu().get_mode() != 1 {
+        gameboy.step_instruction().unwrap();
+    }
+
+    // V-Blank to V-Blank, give or take one 3 M-cycle instruction
+    let frame = gameboy.run_frame().unwrap();
+    assert!(frame.frame_ready);
+    assert!(frame.cycles.abs_diff(PPU_FRAME_CYCLES) < 12);
+}
+
+#[test]
+fn test_vram_write_dropped_in_the_cycle_mode_3_starts() {
+    // 17 M-cycles into OAM scan the write still lands; one later the PPU
+    // enters pixel transfer on the write's own M-cycle
+    for (lead, mode, vram, cpu_view) in [(17, 2, 0x42, 0x42), (18, 3, 0x00, 0xFF)] {
+        // ld [hl], a
+        let mut gameboy = gameboy_with_program(&[0x77]);
+        gameboy.cpu.registers_mut().set_hl(0x8000);
+        gameboy.cpu.registers_mut().a = 0x42;
+
+        let bus = gameboy.cpu.bus_mut();
+        while bus.ppu.get_mode() != 2 {
+            bus.tick(CYCLES_1).unwrap();
+        }
+        for _ in 0..lead {
+            bus.tick(CYCLES_1).unwrap();
+        }
+
+        gameboy.step_instruction().unwrap();
+        assert_eq!(gameboy.ppu().get_mode(), mode);
+        assert_eq!(gameboy.mmu().peek(0x8000).unwrap(), vram);
+
+        // The CPU reads 0xFF from OAM in both modes, from VRAM in mode 3
+        assert_eq!(gameboy.mmu().read_byte(0x8000).unwrap(), cpu_view);
+        assert_eq!(gameboy.mmu().read_byte(0xFE00).unwrap(), 0xFF);
+    }
+}
+
+#[test]
+fn test_reset_returns_every_component_to_power_on() {
+    // ld a, $42; jr @
+    let mut gameboy = gameboy_with_program(&[0x3E, 0x42, 0x18, 0xFE]);
+    gameboy.run_frame().unwrap();
+    gameboy.step_instruction().unwrap();
+    assert_ne!(gameboy.ppu().get_line(), 0);
+
+    gameboy.reset().unwrap();
+    assert_eq!(gameboy.cpu().registers().get_pc(), 0x0100);
+    assert_eq!(gameboy.ppu().get_line(), 0);
+    gameboy.run_frame().unwrap();
+    assert_eq!(gameboy.cpu().registers().a, 0x42);
+}
+
+#[test]
+fn test_save_state_round_trip() {
+    let mut gameboy = gameboy_with_program(&COUNTER_PROGRAM);
+    gameboy.run_frame().unwrap();
+    for _ in 0..1000 {
+        gameboy.step_instruction().unwrap();
+    }
+
+    let state = gameboy.save_state();
+    let counter = gameboy.mmu().read_byte(0xC000).unwrap();
+    gameboy.run_frame().unwrap();
+    assert_ne!(gameboy.mmu().read_byte(0xC000).unwrap(), counter);
+
+    gameboy.load_state(&state).unwrap();
+    assert_eq!(gameboy.mmu().read_byte(0xC000).unwrap(), counter);
+    assert_eq!(gameboy.save_state(), state);
+
+    // A truncated state is rejected without touching the machine
+    gameboy.run_frame().unwrap();
+    let before = gameboy.save_state();
+    assert!(matches!(
+        gameboy.load_state(&state[..state.len() - 1]),
+        Err(Error::State(StateError::Corrupt))
+    ));
+    assert_eq!(gameboy.save_state(), before);
+
+    // So is a state from another cartridge
+    let mut other_rom = rom_with_program(&COUNTER_PROGRAM);
+    other_rom[0x14F] = 0x01;
+    let mut other = gameboy_with_rom(other_rom);
+    assert!(matches!(
+        other.load_state(&state),
+        Err(Error::State(StateError::WrongRom { .. }))
+    ));
+}
+
+#[test]
+fn test_reset_keeps_cartridge_rtc() {
+    // MBC3+TIMER+RAM+BATTERY, 8KB RAM; jr @
+    let mut rom = rom_with_program(&[0x18, 0xFE]);
+    rom[0x147] = 0x10;
+    rom[0x149] = 0x02;
+    let mut gameboy = gameboy_with_rom(rom);
+
+    // Halted so the clock can't tick while the test runs
+    let time = [(0x08, 12), (0x09, 34), (0x0A, 5), (0x0B, 200), (0x0C, 0x41)];
+    let mmu = gameboy.mmu_mut();
+    mmu.write_byte(0x0000, 0x0A).unwrap();
+    for (register, value) in time {
+        mmu.write_byte(0x4000, register).unwrap();
+        mmu.write_byte(0xA000, value).unwrap();
+    }
+
+    gameboy.reset().unwrap();
+
+    let mmu = gameboy.mmu_mut();
+    mmu.write_byte(0x0000, 0x0A).unwrap();
+    mmu.write_byte(0x6000, 0x00).unwrap();
+    mmu.write_byte(0x6000, 0x01).unwrap();
+    for (register, value) in time {
+        mmu.write_byte(0x4000, register).unwrap();
+        assert_eq!(mmu.read_byte(0xA000).unwrap(), value, "{:02X}h", register);
+    }
+}
+
+#[test]
+fn test_rewind_replays_frames_backwards() {
+    let mut gameboy = gameboy_with_program(&COUNTER_PROGRAM);
+
+    let mut rewind = Rewind::new(1024 * 1024, 1);
+    let mut snapshots = Vec::new();
+    for _ in 0..5 {
+        gameboy.run_frame().unwrap();
+        rewind.record(&gameboy);
+        snapshots.push(gameboy.snapshot());
+    }
+
+    // The first step already goes back from the frame on screen
+    for snapshot in snapshots.iter().rev().skip(1) {
+        rewind.rewind(&mut gameboy).unwrap();
+        assert_eq!(&gameboy.snapshot(), snapshot);
+    }
+    // The oldest frame is as far back as it goes
+    assert!(!rewind.rewind(&mut gameboy).unwrap());
+    assert_eq!(&gameboy.snapshot(), &snapshots[0]);
+}
@@ -0,0 +1,298 @@
+// SM83 single-step conformance tests
+//
+// Runs the community JSON vectors (one `xx.json` / `cb xx.json` file per
+// opcode) against the CPU on a flat 64KB bus. Point `SM83_TESTS` at the
+// directory holding them; the test is skipped when it doesn't exist.
+//
+// Some vector sets come from a core that overlaps the next opcode fetch with
+// the last M-cycle: PC starts past an already fetched opcode and the final
+// cycle fetches the next one. The convention is probed once per directory
+// from the first NOP case, or forced with `SM83_TESTS_PREFETCH=1` / `=0`.
+
+use crate::core::bus::{BusActivity, FlatBus};
+use crate::core::cpu::CPU;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_DIR: &str = "tests/sm83/v1";
+
+#[derive(Debug, Deserialize)]
+struct TestCase {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    /// `[address, data, pins]` per M-cycle, `null` when the bus is idle
+    cycles: Vec<Option<Vec<Value>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct State {
+    pc: u16,
+    sp: u16,
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    f: u8,
+    h: u8,
+    l: u8,
+    ime: Option<u8>,
+    ie: Option<u8>,
+    ram: Vec<(u16, u8)>,
+}
+
+/// Pass count for one opcode file
+#[derive(Debug, Default)]
+struct OpcodeResult {
+    passed: usize,
+    total: usize,
+    first_failure: Option<String>,
+}
+
+/// Whether `case` starts with its opcode already fetched: the byte before PC
+/// holds the opcode named in the case
+fn starts_prefetched(case: &TestCase) -> bool {
+    let opcode = case
+        .name
+        .split_whitespace()
+        .next()
+        .and_then(|op| u8::from_str_radix(op, 16).ok());
+    let opcode_address = case.initial.pc.wrapping_sub(1);
+    case.initial
+        .ram
+        .iter()
+        .any(|&(address, value)| address == opcode_address && Some(value) == opcode)
+}
+
+/// The fetch convention of the vectors in `dir`
+fn directory_prefetches(dir: &Path) -> bool {
+    if let Some(value) = std::env::var_os("SM83_TESTS_PREFETCH") {
+        return value != "0";
+    }
+    fs::read_to_string(dir.join("00.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str::<Vec<TestCase>>(&text).ok())
+        .and_then(|cases| cases.first().map(starts_prefetched))
+        .unwrap_or(false)
+}
+
+/// Run one case; `Err` describes the first mismatch. With `prefetched`,
+/// PC is rewound so our opcode fetch comes first.
+fn run_case(case: &TestCase, prefetched: bool) -> Result<(), String> {
+    let initial = &case.initial;
+    let mut bus = FlatBus::new();
+    let memory = bus.memory_mut();
+    for &(address, value) in &initial.ram {
+        memory[address as usize] = value;
+    }
+    if let Some(ie) = initial.ie {
+        memory[0xFFFF] = ie;
+    }
+
+    let rewind = u16::from(prefetched);
+
+    let mut cpu = CPU::new(bus);
+    let registers = cpu.registers_mut();
+    registers.set_af((initial.a as u16) << 8 | initial.f as u16);
+    registers.b = initial.b;
+    registers.c = initial.c;
+    registers.d = initial.d;
+    registers.e = initial.e;
+    registers.h = initial.h;
+    registers.l = initial.l;
+    registers.set_sp(initial.sp);
+    registers.set_pc(initial.pc.wrapping_sub(rewind));
+    cpu.set_ime(initial.ime == Some(1));
+
+    cpu.step().map_err(|e| format!("step failed: {}", e))?;
+
+    let expected = &case.expected;
+    let r = cpu.registers();
+    let actual = [
+        r.a as u16,
+        r.get_af() & 0xFF,
+        r.b as u16,
+        r.c as u16,
+        r.d as u16,
+        r.e as u16,
+        r.h as u16,
+        r.l as u16,
+        r.get_sp(),
+        r.get_pc(),
+    ];
+    let wanted = [
+        expected.a as u16,
+        expected.f as u16,
+        expected.b as u16,
+        expected.c as u16,
+        expected.d as u16,
+        expected.e as u16,
+        expected.h as u16,
+        expected.l as u16,
+        expected.sp,
+        expected.pc.wrapping_sub(rewind),
+    ];
+    for (name, (actual, wanted)) in ["A", "F", "B", "C", "D", "E", "H", "L", "SP", "PC"]
+        .iter()
+        .zip(actual.iter().zip(wanted.iter()))
+    {
+        if actual != wanted {
+            return Err(format!("{}: {:04X}, expected {:04X}", name, actual, wanted));
+        }
+    }
+    if let Some(ime) = expected.ime {
+        if cpu.ime() != (ime == 1) {
+            return Err(format!("IME: {}, expected {}", cpu.ime(), ime == 1));
+        }
+    }
+
+    let bus = cpu.bus();
+    for &(address, value) in &expected.ram {
+        let actual = bus.memory()[address as usize];
+        if actual != value {
+            return Err(format!(
+                "[{:04X}]: {:02X}, expected {:02X}",
+                address, actual, value
+            ));
+        }
+    }
+
+    let wanted: Vec<BusActivity> = case.cycles.iter().map(parse_cycle).collect();
+    let (actual, wanted) = if prefetched {
+        // Leave out our opcode fetch and their fetch of the next one
+        let actual = bus.activity().get(1..).unwrap_or_default();
+        (actual, &wanted[..wanted.len().saturating_sub(1)])
+    } else {
+        (bus.activity(), &wanted[..])
+    };
+    if actual != wanted {
+        return Err(format!("bus: {:?}, expected {:?}", actual, wanted));
+    }
+    Ok(())
+}
+
+fn parse_cycle(cycle: &Option<Vec<Value>>) -> BusActivity {
+    let Some(cycle) = cycle else {
+        return BusActivity::Idle;
+    };
+    let address = cycle.first().and_then(Value::as_u64).unwrap_or(0) as u16;
+    let data = cycle.get(1).and_then(Value::as_u64).unwrap_or(0) as u8;
+    match cycle.get(2).and_then(Value::as_str) {
+        Some(pins) if pins.contains('r') => BusActivity::Read(address, data),
+        Some(pins) if pins.contains('w') => BusActivity::Write(address, data),
+        _ => BusActivity::Idle,
+    }
+}
+
+/// Run every vector file in `dir`, keyed by file name
+fn run_directory(dir: &Path) -> BTreeMap<String, OpcodeResult> {
+    let mut results = BTreeMap::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return results;
+    };
+    let prefetched = directory_prefetches(dir);
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let result: &mut OpcodeResult = results.entry(name.into_owned()).or_default();
+
+        let cases: Vec<TestCase> = match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+        {
+            Ok(cases) => cases,
+            Err(e) => {
+                result.first_failure = Some(format!("unreadable: {}", e));
+                continue;
+            }
+        };
+        for case in &cases {
+            result.total += 1;
+            match run_case(case, prefetched) {
+                Ok(()) => result.passed += 1,
+                Err(e) if result.first_failure.is_none() => {
+                    result.first_failure = Some(format!("{}: {}", case.name, e));
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    results
+}
+
+#[test]
+fn test_sm83_single_step_vectors() {
+    let dir = std::env::var_os("SM83_TESTS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DIR));
+    if !dir.is_dir() {
+        eprintln!("skipping SM83 vectors: {} not found", dir.display());
+        return;
+    }
+
+    let results = run_directory(&dir);
+    let mut failing = Vec::new();
+    for (opcode, result) in &results {
+        let rate = 100.0 * result.passed as f64 / result.total.max(1) as f64;
+        println!(
+            "{:>6}: {:>5}/{:<5} {:6.2}%",
+            opcode, result.passed, result.total, rate
+        );
+        if let Some(failure) = &result.first_failure {
+            println!("        {}", failure);
+            failing.push(opcode.as_str());
+        }
+    }
+    assert!(
+        failing.is_empty(),
+        "failing opcodes: {}",
+        failing.join(", ")
+    );
+}
+
+#[test]
+fn test_single_step_case_compares_bus_activity() {
+    // LD (HL),A in both conventions: fetch first, and fetch overlapped with
+    // the previous instruction
+    let vectors = r#"[
+        {
+            "name": "77 0000",
+            "initial": {"pc": 49152, "sp": 65534, "a": 66, "b": 0, "c": 0, "d": 0,
+                        "e": 0, "f": 0, "h": 208, "l": 0, "ime": 0,
+                        "ram": [[49152, 119]]},
+            "final": {"pc": 49153, "sp": 65534, "a": 66, "b": 0, "c": 0, "d": 0,
+                      "e": 0, "f": 0, "h": 208, "l": 0, "ime": 0,
+                      "ram": [[49152, 119], [53248, 66]]},
+            "cycles": [[49152, 119, "r-m"], [53248, 66, "-wm"]]
+        },
+        {
+            "name": "77 0001",
+            "initial": {"pc": 49153, "sp": 65534, "a": 66, "b": 0, "c": 0, "d": 0,
+                        "e": 0, "f": 0, "h": 208, "l": 0, "ime": 0,
+                        "ram": [[49152, 119], [49153, 0]]},
+            "final": {"pc": 49154, "sp": 65534, "a": 66, "b": 0, "c": 0, "d": 0,
+                      "e": 0, "f": 0, "h": 208, "l": 0, "ime": 0,
+                      "ram": [[53248, 66]]},
+            "cycles": [[53248, 66, "-wm"], [49153, 0, "r-m"]]
+        }
+    ]"#;
+    let mut cases: Vec<TestCase> = serde_json::from_str(vectors).unwrap();
+    assert!(!starts_prefetched(&cases[0]));
+    assert!(starts_prefetched(&cases[1]));
+    assert_eq!(run_case(&cases[0], false), Ok(()));
+    assert_eq!(run_case(&cases[1], true), Ok(()));
+    // Each case fails under the other convention
+    assert!(run_case(&cases[0], true).is_err());
+    assert!(run_case(&cases[1], false).is_err());
+
+    // A write to the wrong address is caught
+    cases[0].expected.ram[1].0 = 53249;
+    assert!(run_case(&cases[0], false).is_err());
+}